@@ -1,23 +1,26 @@
 mod bench_utils;
 
 use criterion::{criterion_group, criterion_main, measurement::WallTime, Criterion};
-use dancinglink::dancinglink_v1::DL;
+use dancinglink::{
+    chooser::{ColumnChooser, FirstColumn, Mrv, MrvRandomTies, WeightedMrv},
+    dancinglink_v1::DL,
+    nodes::{AosNodes, Nodes, SoaNodes},
+    solver::CoverSolver,
+};
 
-fn gen_dl_cases(r: usize, c: usize, solution_rows: usize, times: usize) -> Vec<DL> {
-    let mut dl_vec = vec![];
-    for _ in 0..times {
-        let (matrix, _) = bench_utils::generate_sparse_matrix_with_solution(r, c, solution_rows);
-        let mut dl = DL::new(r, c);
-        for (r_in, c_vec) in matrix.iter().enumerate() {
-            for (c_in, item) in c_vec.iter().enumerate() {
-                if *item == 1 {
-                    dl.insert(r_in + 1, c_in + 1)
-                }
-            }
-        }
-        dl_vec.push(dl);
-    }
-    dl_vec
+// The cols of each row of a random matrix with a solution
+fn gen_rows(r: usize, c: usize, solution_rows: usize) -> Vec<Vec<usize>> {
+    let (matrix, _) = bench_utils::generate_sparse_matrix_with_solution(r, c, solution_rows);
+    matrix
+        .iter()
+        .map(|c_vec| (1..=c).filter(|&c_in| c_vec[c_in - 1] == 1).collect())
+        .collect()
+}
+
+fn gen_dl_cases<S: CoverSolver>(r: usize, c: usize, solution_rows: usize, times: usize) -> Vec<S> {
+    (0..times)
+        .map(|_| S::from_rows(c, &gen_rows(r, c, solution_rows)).unwrap())
+        .collect()
 }
 
 fn group_bench(
@@ -27,13 +30,13 @@ fn group_bench(
     solution_rows: usize,
 ) {
     let id = format!("r{}c{}solution_rows{}", r, c, solution_rows);
-    let cases = gen_dl_cases(r, c, solution_rows, 1);
+    let cases: Vec<DL> = gen_dl_cases(r, c, solution_rows, 1);
     g.bench_function(id, |b| {
         b.iter_batched_ref(
             || cases.clone(),
             |cases| {
                 for case in cases {
-                    let _ = case.dance();
+                    let _ = case.solve();
                 }
             },
             criterion::BatchSize::SmallInput,
@@ -63,9 +66,103 @@ fn benchmark_dl_with_different_rc(c: &mut Criterion) {
     group.finish();
 }
 
+// Dance the same matrix with the node storage N, and print the bytes its nodes take
+fn layout_bench<N: Nodes>(
+    g: &mut criterion::BenchmarkGroup<'_, WallTime>,
+    layout: &str,
+    id: &str,
+    c: usize,
+    rows: &[Vec<usize>],
+) {
+    let id = format!("{}/{}", layout, id);
+    let cases = vec![<DL<N> as CoverSolver>::from_rows(c, rows).unwrap()];
+    println!("{}: {} bytes of nodes", id, cases[0].node_bytes());
+    g.bench_function(id, |b| {
+        b.iter_batched_ref(
+            || cases.clone(),
+            |cases| {
+                for case in cases {
+                    let _ = case.dance();
+                }
+            },
+            criterion::BatchSize::SmallInput,
+        );
+    });
+}
+
+fn benchmark_dl_node_layouts(c: &mut Criterion) {
+    let mut group = c.benchmark_group("DL node layouts");
+    for (r, col, solution_rows) in [(50, 50, 5), (100, 100, 5), (300, 300, 5)] {
+        let id = format!("r{}c{}solution_rows{}", r, col, solution_rows);
+        let rows = gen_rows(r, col, solution_rows);
+        layout_bench::<SoaNodes<usize>>(&mut group, "soa_usize", &id, col, &rows);
+        layout_bench::<SoaNodes<u32>>(&mut group, "soa_u32", &id, col, &rows);
+        layout_bench::<AosNodes<usize>>(&mut group, "aos_usize", &id, col, &rows);
+        layout_bench::<AosNodes<u32>>(&mut group, "aos_u32", &id, col, &rows);
+        layout_bench::<AosNodes<u16>>(&mut group, "aos_u16", &id, col, &rows);
+    }
+    group.finish();
+}
+
+// Dance the same matrices with the column chooser C
+fn chooser_bench<C: ColumnChooser>(
+    g: &mut criterion::BenchmarkGroup<'_, WallTime>,
+    chooser_name: &str,
+    id: &str,
+    cases: &[DL],
+    chooser: C,
+) {
+    let cases: Vec<_> = cases
+        .iter()
+        .map(|dl| dl.clone().with_chooser(chooser.clone()))
+        .collect();
+    g.bench_function(format!("{}/{}", chooser_name, id), |b| {
+        b.iter_batched_ref(
+            || cases.clone(),
+            |cases| {
+                for case in cases {
+                    let _ = case.dance();
+                }
+            },
+            criterion::BatchSize::SmallInput,
+        );
+    });
+}
+
+fn benchmark_dl_column_choosers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("DL column choosers");
+    for (r, col, solution_rows) in [(50, 50, 5), (100, 100, 5)] {
+        let id = format!("r{}c{}solution_rows{}", r, col, solution_rows);
+        let cases: Vec<DL> = gen_dl_cases(r, col, solution_rows, 1);
+        // Prefer the first half of the cols a little
+        let weights: Vec<f64> = (0..col)
+            .map(|i| if i < col / 2 { 1.5 } else { 1.0 })
+            .collect();
+        chooser_bench(&mut group, "mrv", &id, &cases, Mrv);
+        chooser_bench(&mut group, "first", &id, &cases, FirstColumn);
+        chooser_bench(
+            &mut group,
+            "mrv_random_ties",
+            &id,
+            &cases,
+            MrvRandomTies::seeded(0),
+        );
+        chooser_bench(
+            &mut group,
+            "weighted_mrv",
+            &id,
+            &cases,
+            WeightedMrv::new(&weights),
+        );
+    }
+    group.finish();
+}
+
 criterion_group!(
     benches,
     benchmark_dl_with_same_rc,
-    benchmark_dl_with_different_rc
+    benchmark_dl_with_different_rc,
+    benchmark_dl_node_layouts,
+    benchmark_dl_column_choosers
 );
 criterion_main!(benches);
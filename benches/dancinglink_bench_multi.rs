@@ -1,40 +1,30 @@
 mod bench_utils;
 
 use criterion::{criterion_group, criterion_main, measurement::WallTime, Criterion};
-use dancinglink::dancinglink_multicover_v1::DlMulti;
+use dancinglink::{cpp_binding::CppDlx, dancinglink_multicover_v1::DlMulti, solver::CoverSolver};
 
-fn gen_dl_cases(r: usize, c: usize, solution_rows: usize, times: usize) -> Vec<DlMulti> {
-    let mut dl_vec = vec![];
-    for _ in 0..times {
-        let (matrix, _) = bench_utils::generate_muticover_matrix(r, c, solution_rows);
-        let mut dl = DlMulti::new(r, c);
-        for (r_in, c_vec) in matrix.iter().enumerate() {
-            for (c_in, item) in c_vec.iter().enumerate() {
-                if *item == 1 {
-                    dl.insert(r_in + 1, c_in + 1)
-                }
-            }
-        }
-        dl_vec.push(dl);
-    }
-    dl_vec
+fn gen_rows(r: usize, c: usize, solution_rows: usize) -> Vec<Vec<usize>> {
+    let (matrix, _) = bench_utils::generate_muticover_matrix(r, c, solution_rows);
+    matrix
+        .iter()
+        .map(|c_vec| (1..=c).filter(|&c_in| c_vec[c_in - 1] == 1).collect())
+        .collect()
 }
 
-fn group_bench(
+// Build the solver S from the same rows each time, so the engines can be compared
+fn group_bench<S: CoverSolver>(
     g: &mut criterion::BenchmarkGroup<'_, WallTime>,
-    r: usize,
+    engine: &str,
     c: usize,
     solution_rows: usize,
+    rows: &[Vec<usize>],
 ) {
-    let id = format!("r{}c{}solution_rows{}", r, c, solution_rows);
-    let cases = gen_dl_cases(r, c, solution_rows, 1);
+    let id = format!("{engine}r{}c{c}solution_rows{solution_rows}", rows.len());
     g.bench_function(id, |b| {
-        b.iter_batched_ref(
-            || cases.clone(),
-            |cases| {
-                for case in cases {
-                    let _ = case.dance();
-                }
+        b.iter_batched(
+            || S::from_rows(c, rows).unwrap(),
+            |mut solver| {
+                let _ = solver.solve();
             },
             criterion::BatchSize::SmallInput,
         );
@@ -43,20 +33,26 @@ fn group_bench(
 
 fn benchmark_dl_multi_with_different_rc(c: &mut Criterion) {
     let mut group: criterion::BenchmarkGroup<'_, WallTime> = c.benchmark_group("DL multi with different rc");
-    group_bench(&mut group, 10, 10, 5);
-    group_bench(&mut group, 20, 20, 5);
-    group_bench(&mut group, 30, 30, 5);
-    group_bench(&mut group, 40, 40, 5);
-    group_bench(&mut group, 50, 50, 5);
-    group_bench(&mut group, 60, 60, 5);
-    group_bench(&mut group, 100, 100, 5);
-    group_bench(&mut group, 200, 200, 5);
-    group_bench(&mut group, 300, 300, 5);
+    for rc in [10, 20, 30, 40, 50, 60, 100, 200, 300] {
+        group_bench::<DlMulti>(&mut group, "", rc, 5, &gen_rows(rc, rc, 5));
+    }
+    group.finish();
+}
+
+// The C++ DLX searches for the least rows, so it is only run on small matrices
+fn benchmark_multicover_engines(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Multicover engines");
+    for rc in [10, 20, 30] {
+        let rows = gen_rows(rc, rc, 5);
+        group_bench::<DlMulti>(&mut group, "rust/", rc, 5, &rows);
+        group_bench::<CppDlx>(&mut group, "cpp/", rc, 5, &rows);
+    }
     group.finish();
 }
 
 criterion_group!(
     benches_multi,
-    benchmark_dl_multi_with_different_rc
+    benchmark_dl_multi_with_different_rc,
+    benchmark_multicover_engines
 );
 criterion_main!(benches_multi);
@@ -83,7 +83,6 @@ int DLX::f() const
 
 void DLX::dance(int d, vector<int> &path)
 {
-  cout << "d: " << d << endl;
   if (d + f() > ansd)
     return;
   if (d > ansd)
@@ -0,0 +1,143 @@
+use std::fmt::Debug;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// Strategy to choose the column to branch on at each node of the search.
+// Any choice gives the same solutions, only the shape and the size of the search tree change.
+pub trait ColumnChooser: Clone + Debug {
+    // Choose one of columns, each given as (col, rows left in it) in the order of the header list.
+    // columns is never empty.
+    fn choose(&mut self, columns: impl Iterator<Item = (usize, usize)>) -> usize;
+}
+
+// Minimum remaining values: the first column with the least rows, the default of DL
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mrv;
+
+impl ColumnChooser for Mrv {
+    fn choose(&mut self, columns: impl Iterator<Item = (usize, usize)>) -> usize {
+        let mut min = (0, usize::MAX);
+        for (col, size) in columns {
+            if size < min.1 {
+                min = (col, size);
+            }
+        }
+        min.0
+    }
+}
+
+// The first uncovered column, whatever its size
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FirstColumn;
+
+impl ColumnChooser for FirstColumn {
+    fn choose(&mut self, mut columns: impl Iterator<Item = (usize, usize)>) -> usize {
+        columns.next().unwrap().0
+    }
+}
+
+// A column with the least rows, the ties are broken uniformly at random
+#[derive(Debug, Clone)]
+pub struct MrvRandomTies<R = StdRng> {
+    rng: R,
+}
+
+impl MrvRandomTies {
+    pub fn seeded(seed: u64) -> Self {
+        Self::new(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl<R> MrvRandomTies<R> {
+    pub fn new(rng: R) -> Self {
+        MrvRandomTies { rng }
+    }
+}
+
+impl<R: Rng + Clone + Debug> ColumnChooser for MrvRandomTies<R> {
+    fn choose(&mut self, columns: impl Iterator<Item = (usize, usize)>) -> usize {
+        let mut min = (0, usize::MAX);
+        let mut ties = 0;
+        for (col, size) in columns {
+            if size < min.1 {
+                min = (col, size);
+                ties = 1;
+            } else if size == min.1 {
+                // Reservoir sampling, each of the ties is kept with the same probability
+                ties += 1;
+                if self.rng.gen_range(0..ties) == 0 {
+                    min.0 = col;
+                }
+            }
+        }
+        min.0
+    }
+}
+
+// The column with the least size / weight, so a column with more weight is preferred
+// even when it has a few more rows. Cols without a weight weigh 1, and a col with
+// weight 0 is only chosen when every col has weight 0.
+// An empty column is always chosen at once, as the branch is dead.
+#[derive(Debug, Clone, Default)]
+pub struct WeightedMrv {
+    weights: Vec<f64>, // weights[col], starting with 1
+}
+
+impl WeightedMrv {
+    // weights[i] is the weight of col i + 1
+    pub fn new(weights: &[f64]) -> Self {
+        let mut all = vec![1.0];
+        all.extend_from_slice(weights);
+        WeightedMrv { weights: all }
+    }
+
+    fn weight(&self, col: usize) -> f64 {
+        self.weights.get(col).copied().unwrap_or(1.0)
+    }
+}
+
+impl ColumnChooser for WeightedMrv {
+    fn choose(&mut self, columns: impl Iterator<Item = (usize, usize)>) -> usize {
+        let mut min = (0, f64::INFINITY);
+        for (col, size) in columns {
+            if size == 0 {
+                return col;
+            }
+            let weight = self.weight(col);
+            let score = if weight > 0.0 {
+                size as f64 / weight
+            } else {
+                f64::INFINITY
+            };
+            if min.0 == 0 || score < min.1 {
+                min = (col, score);
+            }
+        }
+        min.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_choosers() {
+        let columns = [(1, 3), (2, 1), (3, 2), (4, 1)];
+        assert_eq!(Mrv.choose(columns.into_iter()), 2);
+        assert_eq!(FirstColumn.choose(columns.into_iter()), 1);
+
+        let mut chooser = MrvRandomTies::seeded(7);
+        let mut seen = [false; 5];
+        for _ in 0..100 {
+            seen[chooser.choose(columns.into_iter())] = true;
+        }
+        assert_eq!(seen, [false, false, true, false, true]);
+
+        let mut chooser = WeightedMrv::new(&[4.0, 1.0, 0.0]);
+        assert_eq!(chooser.choose(columns.into_iter()), 1);
+        assert_eq!(chooser.choose([(3, 1), (4, 2)].into_iter()), 4);
+        assert_eq!(chooser.choose([(3, 1)].into_iter()), 3);
+        assert_eq!(chooser.choose([(1, 3), (2, 0)].into_iter()), 2);
+    }
+}
@@ -1,4 +1,6 @@
-#![cfg(test)]
+use cxx::CxxVector;
+
+use crate::{error::DlxError, solver::CoverSolver, stats::SearchStats};
 
 pub use ffi::*;
 
@@ -10,7 +12,7 @@ mod ffi {
         include!("dancinglink/cpp/include/dancing_link.h");
 
         type DLX;
-        
+
         fn new_DLX() -> UniquePtr<DLX>;
         fn init(self: Pin<&mut DLX>, row: i32, col: i32);
         fn Link(self: Pin<&mut DLX>, row: i32, col: i32);
@@ -22,6 +24,78 @@ mod ffi {
     }
 }
 
+// The C++ DLX behind CoverSolver. It finds a cover with the least rows where each col
+// is covered at least once, like DlMulti::min_cover, and can neither enumerate nor count.
+// The rows are kept and linked into a new DLX for each search, as DLX keeps its best depth.
+pub struct CppDlx {
+    col_size: usize,
+    rows: Vec<Vec<usize>>,
+}
+
+// The size of the node arrays of DLX in cpp/include/dancing_link.h
+const DLX_NODES: usize = 5002;
+
+impl CoverSolver for CppDlx {
+    fn from_rows(col_size: usize, rows: &[Vec<usize>]) -> Result<Self, DlxError> {
+        let nodes = 1 + col_size + rows.iter().map(Vec::len).sum::<usize>();
+        if nodes > DLX_NODES || rows.len() >= DLX_NODES {
+            return Err(DlxError::InvalidInput(format!(
+                "{} rows with {nodes} nodes do not fit in the {DLX_NODES} nodes of DLX",
+                rows.len()
+            )));
+        }
+        for (r_in, cols) in rows.iter().enumerate() {
+            for (i, &col) in cols.iter().enumerate() {
+                if col == 0 || col > col_size || cols[..i].contains(&col) {
+                    return Err(DlxError::InvalidInput(format!(
+                        "insert ({}, {col}), but cols are 1..={col_size} and each only once",
+                        r_in + 1
+                    )));
+                }
+            }
+        }
+        Ok(CppDlx {
+            col_size,
+            rows: rows.to_vec(),
+        })
+    }
+
+    fn solve(&mut self) -> Result<Vec<usize>, DlxError> {
+        let mut dlx = new_DLX();
+        let mut path = CxxVector::<i32>::new();
+        dlx.pin_mut()
+            .init(self.rows.len() as i32, self.col_size as i32);
+        for (r_in, cols) in self.rows.iter().enumerate() {
+            for &col in cols {
+                dlx.pin_mut().Link(r_in as i32 + 1, col as i32);
+            }
+        }
+        dlx.pin_mut().dance(0, path.pin_mut());
+        let res: Vec<usize> = dlx.get_res().iter().map(|&row| row as usize).collect();
+        // Without cols the empty cover is the answer, otherwise a cover has a row
+        if res.is_empty() && self.col_size > 0 {
+            return Err(DlxError::NoSolution);
+        }
+        Ok(res)
+    }
+
+    fn solve_all(&mut self) -> Result<Vec<Vec<usize>>, DlxError> {
+        Err(DlxError::Unsupported(
+            "the C++ DLX only finds one cover".to_string(),
+        ))
+    }
+
+    fn count(&mut self, _limit: Option<u64>) -> Result<u64, DlxError> {
+        Err(DlxError::Unsupported(
+            "the C++ DLX can not count covers".to_string(),
+        ))
+    }
+
+    fn search_stats(&self) -> Option<&SearchStats> {
+        None
+    }
+}
+
 #[cfg(test)]
 mod test {
     use cxx::CxxVector;
@@ -43,4 +117,4 @@ mod test {
         println!("Result is: ");
         res.iter().for_each(|x| println!("{}", *x));
     }
-}
\ No newline at end of file
+}
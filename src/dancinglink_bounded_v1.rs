@@ -0,0 +1,648 @@
+use std::fmt::Display;
+
+use crate::{error::DlxError, utils};
+
+// Each column carries a multiplicity [lo, hi]: a solution is a set of rows
+// that covers every column at least lo and at most hi times.
+// [1, 1] is exact cover, [1, usize::MAX] is multicover.
+// Code Reference: Algorithm M in TAOCP 7.2.2.1
+
+#[allow(non_snake_case)]
+#[derive(Debug, Clone)]
+pub struct DlBounded {
+    r: usize, // row size
+    c: usize, // col size
+    idx: usize,
+    first: Vec<usize>,
+    size: Vec<isize>,
+    row: Vec<usize>,
+    col: Vec<usize>,
+    lo: Vec<usize>,
+    hi: Vec<usize>,
+    cnt: Vec<usize>, // how many chosen rows cover the col now
+    L: Vec<usize>,
+    R: Vec<usize>,
+    U: Vec<usize>,
+    D: Vec<usize>,
+}
+
+const DEFAULT_ROW: usize = 10;
+const DEFAULT_COL: usize = 10;
+
+impl DlBounded {
+    // All the cols start with [1, 1], use set_bounds to change them
+    #[allow(non_snake_case)]
+    pub fn new(row_size: usize, col_size: usize) -> Self {
+        let row_size = if row_size < 1 { DEFAULT_ROW } else { row_size };
+        let col_size = if col_size < 1 { DEFAULT_COL } else { col_size };
+        // Only the virtual row is allocated here, the other nodes are pushed when inserting
+        let idx_max = col_size + 1;
+        // Actually, we do not use idx 0, so idx in first and size starts with 1
+        let first = vec![0; row_size + 1];
+        let size = vec![0; col_size + 1];
+        let row = vec![0; idx_max];
+        let col = vec![0; idx_max];
+        let lo = vec![1; col_size + 1];
+        let hi = vec![1; col_size + 1];
+        let cnt = vec![0; col_size + 1];
+        let mut L = vec![0; idx_max];
+        let mut R = vec![0; idx_max];
+        let mut U = vec![0; idx_max];
+        let mut D = vec![0; idx_max];
+
+        // We build a new virtual row, but we don't add them to row or col vectors
+        // Note: Extra idx 0 element
+        for i in 0..=col_size {
+            // In virtual row, we link each element to itself vertically
+            D[i] = i;
+            U[i] = i;
+            // In the horizontal direction, the elements in the virtual row link to each other
+            // ->0->1->...->col_size->
+            // <-0->1<-...<-col_size<-
+            L[i] = if i != 0 { i - 1 } else { col_size };
+            R[i] = if i != col_size { i + 1 } else { 0 };
+        }
+
+        // We maintain a global idx and it is in ascending order
+        // when we are constructing this Cross-Linked List
+        let idx = col_size;
+
+        DlBounded {
+            r: row_size,
+            c: col_size,
+            idx,
+            first,
+            size,
+            row,
+            col,
+            lo,
+            hi,
+            cnt,
+            L,
+            R,
+            U,
+            D,
+        }
+    }
+
+    // Same as new, but the nodes of nonzeros elements are allocated at once
+    pub fn with_capacity(row_size: usize, col_size: usize, nonzeros: usize) -> Self {
+        let mut dl = Self::new(row_size, col_size);
+        dl.reserve(nonzeros);
+        dl
+    }
+
+    // col should be covered between lo and hi times (both inclusive)
+    pub fn set_bounds(&mut self, col: usize, lo: usize, hi: usize) -> Result<(), DlxError> {
+        if col == 0 || col > self.c {
+            return Err(DlxError::InvalidInput(format!(
+                "set bounds of col {col}, but cols are 1..={}",
+                self.c
+            )));
+        }
+        if lo > hi || hi == 0 {
+            return Err(DlxError::InvalidInput(format!(
+                "bounds [{lo}, {hi}] of col {col} should satisfy lo <= hi and hi >= 1"
+            )));
+        }
+        self.lo[col] = lo;
+        self.hi[col] = hi;
+        Ok(())
+    }
+
+    // row and col idx starts with 1
+    pub fn insert(&mut self, row: usize, col: usize) -> Result<(), DlxError> {
+        if row == 0 || row > self.r || col == 0 || col > self.c {
+            return Err(DlxError::InvalidInput(format!(
+                "insert ({row}, {col}) is out of index, rows are 1..={} and cols are 1..={}",
+                self.r, self.c
+            )));
+        }
+        if self.contains(row, col) {
+            return Err(DlxError::InvalidInput(format!(
+                "insert ({row}, {col}) twice"
+            )));
+        }
+        // Because of an extra 0 idx, when we insert a elem, we should ++idx first
+        self.idx += 1;
+        self.push_node();
+        self.row[self.idx] = row;
+        self.col[self.idx] = col;
+        self.size[col] += 1;
+        // Idx directly links to col element in virtual row
+        // Like head insert in linked list
+        self.U[self.idx] = col;
+        self.D[self.idx] = self.D[col];
+        self.U[self.D[col]] = self.idx;
+        self.D[col] = self.idx;
+        // Condition 1
+        // There is no element in row r, we directly insert it into this row
+        // and let the point first point to this element
+        if self.first[row] == 0 {
+            self.first[row] = self.idx;
+            self.L[self.idx] = self.idx;
+            self.R[self.idx] = self.idx;
+        } else {
+            // Once the first[row] is not 0, it can't be change when inserting
+            // So, we insert a new element after element the first[row] points to
+            self.L[self.idx] = self.first[row];
+            self.R[self.idx] = self.R[self.first[row]];
+            self.L[self.R[self.first[row]]] = self.idx;
+            self.R[self.first[row]] = self.idx;
+        }
+        Ok(())
+    }
+
+    // Reserve nodes for at least additional more elements,
+    // e.g. the number of ones in the matrix before inserting them
+    pub fn reserve(&mut self, additional: usize) {
+        self.row.reserve(additional);
+        self.col.reserve(additional);
+        self.L.reserve(additional);
+        self.R.reserve(additional);
+        self.U.reserve(additional);
+        self.D.reserve(additional);
+    }
+
+    // Allocate a node at the end, its fields are set by insert
+    fn push_node(&mut self) {
+        self.row.push(0);
+        self.col.push(0);
+        self.L.push(0);
+        self.R.push(0);
+        self.U.push(0);
+        self.D.push(0);
+    }
+
+    // Whether there is already an element at (row, col)
+    fn contains(&self, row: usize, col: usize) -> bool {
+        let first_idx = self.first[row];
+        if first_idx == 0 {
+            return false;
+        }
+        let mut idx = first_idx;
+        loop {
+            if self.col[idx] == col {
+                return true;
+            }
+            idx = self.R[idx];
+            if idx == first_idx {
+                return false;
+            }
+        }
+    }
+
+    fn get_2d_vec(&self) -> Vec<Vec<usize>> {
+        let mut res_vec = vec![vec![0; self.c + 1]; self.r + 1];
+        for (r, row) in res_vec.iter_mut().enumerate().take(self.r + 1).skip(1) {
+            let first_idx = self.first[r];
+            let mut idx = first_idx;
+            while idx != 0 && self.L[idx] != 0 {
+                row[self.col[idx]] = 1;
+                idx = self.R[idx];
+                if idx == first_idx {
+                    break;
+                }
+            }
+        }
+        res_vec
+    }
+}
+
+// It is maybe only correct before any removal operation.
+impl Display for DlBounded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str_ = utils::format_2d_string(&self.get_2d_vec());
+        write!(f, "{str_}")
+    }
+}
+
+impl DlBounded {
+    // Remove the column from the virtual row and hide the rows in it,
+    // it is done when the column can not be covered any more.
+    fn remove(&mut self, col: usize) {
+        let mut vertical_idx = self.D[col];
+        let mut horizontal_idx;
+        self.L[self.R[col]] = self.L[col];
+        self.R[self.L[col]] = self.R[col];
+        while vertical_idx != col {
+            horizontal_idx = self.R[vertical_idx];
+            while horizontal_idx != vertical_idx {
+                self.U[self.D[horizontal_idx]] = self.U[horizontal_idx];
+                self.D[self.U[horizontal_idx]] = self.D[horizontal_idx];
+                self.size[self.col[horizontal_idx]] -= 1;
+                horizontal_idx = self.R[horizontal_idx];
+            }
+            vertical_idx = self.D[vertical_idx];
+        }
+    }
+
+    // In this function, we will restore the column and the corresponding rows that were previously removed.
+    fn recover(&mut self, col: usize) {
+        let mut vertical_idx = self.U[col];
+        let mut horizontal_idx;
+        while vertical_idx != col {
+            horizontal_idx = self.L[vertical_idx];
+            while horizontal_idx != vertical_idx {
+                self.U[self.D[horizontal_idx]] = horizontal_idx;
+                self.D[self.U[horizontal_idx]] = horizontal_idx;
+                self.size[self.col[horizontal_idx]] += 1;
+                horizontal_idx = self.L[horizontal_idx];
+            }
+            vertical_idx = self.U[vertical_idx];
+        }
+        self.L[self.R[col]] = col;
+        self.R[self.L[col]] = col;
+    }
+
+    // Unlink every element of the row that node belongs to (node included) from its column,
+    // so the row can not be chosen again.
+    fn hide_row(&mut self, node: usize) {
+        let mut horizontal_idx = node;
+        loop {
+            self.U[self.D[horizontal_idx]] = self.U[horizontal_idx];
+            self.D[self.U[horizontal_idx]] = self.D[horizontal_idx];
+            self.size[self.col[horizontal_idx]] -= 1;
+            horizontal_idx = self.R[horizontal_idx];
+            if horizontal_idx == node {
+                break;
+            }
+        }
+    }
+
+    // Reverse of hide_row, node is the last one to be linked back
+    fn unhide_row(&mut self, node: usize) {
+        let mut horizontal_idx = node;
+        loop {
+            horizontal_idx = self.L[horizontal_idx];
+            self.U[self.D[horizontal_idx]] = horizontal_idx;
+            self.D[self.U[horizontal_idx]] = horizontal_idx;
+            self.size[self.col[horizontal_idx]] += 1;
+            if horizontal_idx == node {
+                break;
+            }
+        }
+    }
+
+    // Choose the row that node belongs to: every column in it is covered one more time,
+    // and the columns reaching hi are removed.
+    fn select(&mut self, node: usize) {
+        self.hide_row(node);
+        let mut horizontal_idx = node;
+        loop {
+            let col = self.col[horizontal_idx];
+            self.cnt[col] += 1;
+            if self.cnt[col] == self.hi[col] {
+                self.remove(col);
+            }
+            horizontal_idx = self.R[horizontal_idx];
+            if horizontal_idx == node {
+                break;
+            }
+        }
+    }
+
+    // Reverse of select
+    fn unselect(&mut self, node: usize) {
+        let mut horizontal_idx = node;
+        loop {
+            horizontal_idx = self.L[horizontal_idx];
+            let col = self.col[horizontal_idx];
+            if self.cnt[col] == self.hi[col] {
+                self.recover(col);
+            }
+            self.cnt[col] -= 1;
+            if horizontal_idx == node {
+                break;
+            }
+        }
+        self.unhide_row(node);
+    }
+
+    // Choose the column with the least branches.
+    // A column has a branch for each row in it, plus one more for "no more rows"
+    // if it is already covered lo times.
+    // 0 branches means it can not reach lo any more.
+    fn min_column(&self) -> (usize, usize) {
+        let mut min = self.R[0];
+        let mut min_branch = usize::MAX;
+        let mut horizontal_idx = self.R[0];
+        while horizontal_idx != 0 {
+            let size = self.size[horizontal_idx] as usize;
+            let need = self.lo[horizontal_idx].saturating_sub(self.cnt[horizontal_idx]);
+            let branch = match need {
+                0 => size + 1,
+                need if need > size => 0,
+                _ => size,
+            };
+            if branch < min_branch {
+                min = horizontal_idx;
+                min_branch = branch;
+            }
+            horizontal_idx = self.R[horizontal_idx]
+        }
+        (min, min_branch)
+    }
+
+    // Drive the search to the next solution with an explicit stack.
+    // The rows tried in a column are excluded (pushed into excluded) before trying the next one,
+    // so every set of rows is found only once.
+    // If resume is true, frames hold the last solution and we backtrack from it first.
+    // Return false once the whole search tree is visited.
+    fn search_next(
+        &mut self,
+        frames: &mut Vec<Frame>,
+        excluded: &mut Vec<usize>,
+        resume: bool,
+    ) -> bool {
+        let mut descend = !resume;
+        loop {
+            if descend {
+                // if empty, every column is finished
+                if self.R[0] == 0 {
+                    return true;
+                }
+                let (min, branch) = self.min_column();
+                if branch == 0 {
+                    descend = false;
+                    continue;
+                }
+                frames.push(Frame {
+                    col: min,
+                    node: min,
+                    closed: false,
+                    excluded: excluded.len(),
+                });
+            } else if frames.is_empty() {
+                return false;
+            }
+            let frame = frames.last_mut().unwrap();
+            let col = frame.col;
+            if frame.closed {
+                // Every branch of this column has been tried, go back to the upper level
+                self.recover(col);
+                self.unexclude(excluded, frame.excluded);
+                frames.pop();
+                descend = false;
+                continue;
+            }
+            let next = if frame.node != col {
+                // Leave the row we are trying and exclude it from the following branches
+                self.unselect(frame.node);
+                self.hide_row(frame.node);
+                excluded.push(frame.node);
+                self.D[frame.node]
+            } else {
+                self.D[col]
+            };
+            if next != col {
+                self.select(next);
+                frame.node = next;
+                descend = true;
+            } else if self.cnt[col] >= self.lo[col] {
+                // The last branch: no more rows for this column
+                self.remove(col);
+                frame.node = col;
+                frame.closed = true;
+                descend = true;
+            } else {
+                self.unexclude(excluded, frame.excluded);
+                frames.pop();
+                descend = false;
+            }
+        }
+    }
+
+    // Bring back the rows excluded after the first len ones, in the reverse order
+    fn unexclude(&mut self, excluded: &mut Vec<usize>, len: usize) {
+        while excluded.len() > len {
+            let node = excluded.pop().unwrap();
+            self.unhide_row(node);
+        }
+    }
+
+    // Undo all the frames left on the stack, so the matrix is the same as before the search
+    fn unwind(&mut self, frames: &mut Vec<Frame>, excluded: &mut Vec<usize>) {
+        while let Some(frame) = frames.pop() {
+            if frame.closed {
+                self.recover(frame.col);
+            } else if frame.node != frame.col {
+                self.unselect(frame.node);
+            }
+            self.unexclude(excluded, frame.excluded);
+        }
+    }
+
+    // Return every solution, each one as a list of row ids (starting with 1).
+    pub fn all_solutions(&mut self) -> Vec<Vec<usize>> {
+        self.solutions().collect()
+    }
+
+    // Lazily enumerate the solutions, the next one is only searched when it is asked for.
+    pub fn solutions(&mut self) -> Solutions<'_> {
+        Solutions {
+            dl: self,
+            frames: vec![],
+            excluded: vec![],
+            started: false,
+            done: false,
+        }
+    }
+
+    // Count the solutions without building them, stop at limit if it is given.
+    pub fn count_solutions(&mut self, limit: Option<u64>) -> u64 {
+        let mut frames = vec![];
+        let mut excluded = vec![];
+        let mut count = 0;
+        while limit.is_none_or(|limit| count < limit)
+            && self.search_next(&mut frames, &mut excluded, count > 0)
+        {
+            count += 1;
+        }
+        self.unwind(&mut frames, &mut excluded);
+        count
+    }
+
+    pub fn dance(&mut self) -> Result<Vec<usize>, DlxError> {
+        tracing::info!("Into dancing");
+        self.solutions().next().ok_or(DlxError::NoSolution)
+    }
+}
+
+// One level of the search: the chosen column and the row node we are trying in it.
+// node == col means no row is being tried, and closed means we are in the "no more rows" branch.
+// excluded is the length of the excluded stack when the frame is pushed.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    col: usize,
+    node: usize,
+    closed: bool,
+    excluded: usize,
+}
+
+// Iterator returned by DlBounded::solutions.
+// The matrix is borrowed during the enumeration and restored when it is dropped.
+pub struct Solutions<'a> {
+    dl: &'a mut DlBounded,
+    frames: Vec<Frame>,
+    excluded: Vec<usize>,
+    started: bool,
+    done: bool,
+}
+
+impl Iterator for Solutions<'_> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let found = self
+            .dl
+            .search_next(&mut self.frames, &mut self.excluded, self.started);
+        self.started = true;
+        if !found {
+            self.done = true;
+            return None;
+        }
+        Some(
+            self.frames
+                .iter()
+                .filter(|f| !f.closed && f.node != f.col)
+                .map(|f| self.dl.row[f.node])
+                .collect(),
+        )
+    }
+}
+
+impl Drop for Solutions<'_> {
+    fn drop(&mut self) {
+        self.dl.unwind(&mut self.frames, &mut self.excluded);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use crate::{
+        dancinglink_v1::DL,
+        test_utils,
+        utils::{self, Matrix},
+    };
+
+    use super::*;
+
+    fn build(case: &[Vec<usize>], bounds: &[(usize, usize)]) -> DlBounded {
+        let mut dl = DlBounded::new(case.len(), bounds.len());
+        for (c_in, &(lo, hi)) in bounds.iter().enumerate() {
+            dl.set_bounds(c_in + 1, lo, hi).unwrap();
+        }
+        let (_, rows) = utils::dense_rows(case).unwrap();
+        for (r_in, cols) in rows.iter().enumerate() {
+            for &col in cols {
+                dl.insert(r_in + 1, col).unwrap();
+            }
+        }
+        dl
+    }
+
+    fn sorted(sols: Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+        let mut sols: Vec<_> = sols
+            .into_iter()
+            .map(|mut sol| {
+                sol.sort();
+                sol
+            })
+            .collect();
+        sols.sort();
+        sols
+    }
+
+    #[test]
+    fn test_bounded() {
+        // Rows {1}, {1, 2}, {2}, {2, 3}
+        let case = vec![vec![1, 0, 0], vec![1, 1, 0], vec![0, 1, 0], vec![0, 1, 1]];
+        // Col 2 covered twice, the others once
+        let mut dl = build(&case, &[(1, 1), (2, 2), (1, 1)]);
+        assert_eq!(sorted(dl.all_solutions()), vec![vec![1, 3, 4], vec![2, 4]]);
+        // Col 2 covered once or twice
+        let mut dl = build(&case, &[(1, 1), (1, 2), (1, 1)]);
+        assert_eq!(
+            sorted(dl.all_solutions()),
+            vec![vec![1, 3, 4], vec![1, 4], vec![2, 4]]
+        );
+        assert_eq!(dl.count_solutions(Some(2)), 2);
+        // Col 2 is optional
+        let mut dl = build(&case, &[(1, 1), (0, 1), (1, 1)]);
+        assert_eq!(sorted(dl.all_solutions()), vec![vec![1, 4]]);
+
+        assert!(matches!(
+            dl.set_bounds(1, 2, 1),
+            Err(DlxError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            dl.set_bounds(4, 1, 1),
+            Err(DlxError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_bounded_drop_restore() {
+        let case = vec![vec![1, 1], vec![1, 0], vec![0, 1], vec![1, 1]];
+        let mut dl = build(&case, &[(1, usize::MAX), (1, 2)]);
+        let count = dl.count_solutions(None);
+        let mut solutions = dl.solutions();
+        assert!(solutions.next().is_some());
+        drop(solutions);
+        assert_eq!(dl.count_solutions(None), count);
+        assert!(dl.cnt.iter().all(|&cnt| cnt == 0));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(200))]
+        #[test]
+        fn test_bounded_brute_force(
+            (case, bounds) in (1..=8usize, 1..=5usize).prop_flat_map(|(r, c)| {
+                let item = prop::bool::weighted(0.4).prop_map(usize::from);
+                let bound = (0..=2usize).prop_flat_map(|lo| (Just(lo), lo.max(1)..=3));
+                (
+                    prop::collection::vec(prop::collection::vec(item, c), r),
+                    prop::collection::vec(bound, c),
+                )
+            })
+        ) {
+            let (r, c) = (case.len(), bounds.len());
+            let mut dl = build(&case, &bounds);
+            let sols = sorted(dl.all_solutions());
+
+            let mut expected = vec![];
+            for mask in 0u64..(1 << r) {
+                let sol: Vec<_> = (1..=r).filter(|row| mask & (1 << (row - 1)) != 0).collect();
+                // Empty rows can not be chosen
+                if sol.iter().any(|row| case[row - 1].iter().all(|&x| x == 0)) {
+                    continue;
+                }
+                let ok = (0..c).all(|col| {
+                    let sum: usize = sol.iter().map(|row| case[row - 1][col]).sum();
+                    bounds[col].0 <= sum && sum <= bounds[col].1
+                });
+                if ok {
+                    expected.push(sol);
+                }
+            }
+            expected.sort();
+            prop_assert_eq!(dl.count_solutions(None), expected.len() as u64);
+            prop_assert_eq!(sols, expected);
+        }
+
+        #[test]
+        fn test_bounded_exact_cover(case in test_utils::matrix_strategy(1..=10, 1..=8)) {
+            let c = case[0].len();
+            let mut dl = build(&case, &vec![(1, 1); c]);
+            let mut exact = DL::try_from(&Matrix(case.clone())).unwrap();
+            prop_assert_eq!(sorted(dl.all_solutions()), sorted(exact.all_solutions()));
+            prop_assert_eq!(sorted(dl.all_solutions()), test_utils::brute_force_exact_covers(&case));
+        }
+    }
+}
@@ -1,7 +1,12 @@
 
 use std::fmt::Display;
 
-use crate::utils;
+use crate::{
+    error::DlxError,
+    solver::CoverSolver,
+    stats::SearchStats,
+    utils::{self, Matrix},
+};
 
 #[allow(non_snake_case)]
 #[derive(Debug, Clone)]
@@ -17,19 +22,24 @@ pub struct DlMulti {
     R: Vec<usize>,
     U: Vec<usize>,
     D: Vec<usize>,
-    res: Option<Vec<usize>>, // usize: the max deep when dancing
+    stats: SearchStats, // counters of the last search
 }
 
 const DEFAULT_ROW: usize = 10;
 const DEFAULT_COL: usize = 10;
-const MAX_DEEP: usize = 500;
 
 impl DlMulti {
-    #[allow(non_snake_case)]
     pub fn new(row_size: usize, col_size: usize) -> Self {
         let row_size = if row_size < 1 { DEFAULT_ROW } else { row_size };
         let col_size = if col_size < 1 { DEFAULT_COL } else { col_size };
-        let idx_max = row_size * col_size + 1;
+        Self::with_size(row_size, col_size)
+    }
+
+    // Same as new, but an empty size is kept as it is
+    #[allow(non_snake_case)]
+    fn with_size(row_size: usize, col_size: usize) -> Self {
+        // Only the virtual row is allocated here, the other nodes are pushed when inserting
+        let idx_max = col_size + 1;
         // Actually, we do not use idx 0, so idx in first and size starts with 1
         let first = vec![0; row_size + 1];
         let size = vec![0; col_size + 1];
@@ -39,7 +49,6 @@ impl DlMulti {
         let mut R = vec![0; idx_max];
         let mut U = vec![0; idx_max];
         let mut D = vec![0; idx_max];
-        let res = None;
 
         // We build a new virtual row, but we don't add them to row or col vectors
         // Note: Extra idx 0 element
@@ -70,18 +79,69 @@ impl DlMulti {
             R,
             U,
             D,
-            res,
+            stats: SearchStats::default(),
+        }
+    }
+
+    // Same as new, but the nodes of nonzeros elements are allocated at once
+    pub fn with_capacity(row_size: usize, col_size: usize, nonzeros: usize) -> Self {
+        let mut dl = Self::new(row_size, col_size);
+        dl.reserve(nonzeros);
+        dl
+    }
+
+    // The i-th item of rows lists the cols of row i + 1, as DL::from_rows
+    pub fn from_rows<R: IntoIterator<Item = usize>>(
+        col_size: usize,
+        rows: impl IntoIterator<Item = R>,
+    ) -> Result<Self, DlxError> {
+        let rows: Vec<Vec<usize>> = rows.into_iter().map(|r| r.into_iter().collect()).collect();
+        Self::with_rows(col_size, &rows)
+    }
+
+    // The cols of the matrix are the cols of the first row
+    pub fn from_dense(matrix: &[Vec<bool>]) -> Result<Self, DlxError> {
+        let (col_size, rows) = utils::dense_rows(matrix)?;
+        Self::with_rows(col_size, &rows)
+    }
+
+    // A matrix in CSR form, as DL::from_sparse
+    pub fn from_sparse(
+        col_size: usize,
+        indptr: &[usize],
+        indices: &[usize],
+    ) -> Result<Self, DlxError> {
+        let rows = utils::sparse_rows(col_size, indptr, indices)?;
+        Self::with_rows(col_size, &rows)
+    }
+
+    fn with_rows(col_size: usize, rows: &[Vec<usize>]) -> Result<Self, DlxError> {
+        let mut dl = Self::with_size(rows.len(), col_size);
+        dl.reserve(rows.iter().map(Vec::len).sum());
+        for (r_in, cols) in rows.iter().enumerate() {
+            for &col in cols {
+                dl.insert(r_in + 1, col)?;
+            }
         }
+        Ok(dl)
     }
 
     // row and col idx starts with 1
-    pub fn insert(&mut self, row: usize, col: usize) {
-        assert!(
-            row <= self.r && col <= self.c,
-            "Insert: row or col is out of index"
-        );
+    pub fn insert(&mut self, row: usize, col: usize) -> Result<(), DlxError> {
+        if row == 0 || row > self.r || col == 0 || col > self.c {
+            return Err(DlxError::InvalidInput(format!(
+                "insert ({row}, {col}) is out of index, rows are 1..={} and cols are 1..={}",
+                self.r, self.c
+            )));
+        }
+        if self.contains(row, col) {
+            return Err(DlxError::InvalidInput(format!(
+                "insert ({row}, {col}) twice"
+            )));
+        }
         // Because of an extra 0 idx, when we insert a elem, we should ++idx first
         self.idx += 1;
+        self.push_node();
         self.row[self.idx] = row;
         self.col[self.idx] = col;
         self.size[col] += 1;
@@ -106,6 +166,46 @@ impl DlMulti {
             self.L[self.R[self.first[row]]] = self.idx;
             self.R[self.first[row]] = self.idx;
         }
+        Ok(())
+    }
+
+    // Reserve nodes for at least additional more elements,
+    // e.g. the number of ones in the matrix before inserting them
+    pub fn reserve(&mut self, additional: usize) {
+        self.row.reserve(additional);
+        self.col.reserve(additional);
+        self.L.reserve(additional);
+        self.R.reserve(additional);
+        self.U.reserve(additional);
+        self.D.reserve(additional);
+    }
+
+    // Allocate a node at the end, its fields are set by insert
+    fn push_node(&mut self) {
+        self.row.push(0);
+        self.col.push(0);
+        self.L.push(0);
+        self.R.push(0);
+        self.U.push(0);
+        self.D.push(0);
+    }
+
+    // Whether there is already an element at (row, col)
+    fn contains(&self, row: usize, col: usize) -> bool {
+        let first_idx = self.first[row];
+        if first_idx == 0 {
+            return false;
+        }
+        let mut idx = first_idx;
+        loop {
+            if self.col[idx] == col {
+                return true;
+            }
+            idx = self.R[idx];
+            if idx == first_idx {
+                return false;
+            }
+        }
     }
 
     fn get_2d_vec(&self) -> Vec<Vec<usize>> {
@@ -138,25 +238,18 @@ impl DlMulti {
     fn remove(&mut self, col: usize) {
         self.L[self.R[col]] = self.L[col];
         self.R[self.L[col]] = self.R[col];
+        self.stats.updates += 1;
     }
 
     // In this function, we will restore the column.
     fn recover(&mut self, col: usize) {
         self.L[self.R[col]] = col;
         self.R[self.L[col]] = col;
+        self.stats.updates += 1;
     }
 
-    fn dance_internal(&mut self, deep: usize) -> bool {
-        tracing::info!("Into dancing internal");
-        // if empty, return false
-        if self.R[0] == 0 {
-            // In external function, we should ensure self.res is not None
-            if let Some(res_vec) = self.res.as_mut() {
-                res_vec.truncate(deep)
-            }
-            return true;
-        }
-        // Choose the column with least elements
+    // Choose the column with least elements
+    fn min_column(&self) -> usize {
         let mut min = self.R[0];
         let mut horizontal_idx = self.R[0];
         while horizontal_idx != 0 {
@@ -166,37 +259,243 @@ impl DlMulti {
             }
             horizontal_idx = self.R[horizontal_idx]
         }
-        tracing::info!("Choose column: {}, self.R: {:?}", min, self.R);
-        // Attemp to remove the selected column
-        self.remove(min);
-
-        let mut vertical_idx = self.D[min];
-        while vertical_idx != min {
-            self.res.as_mut().unwrap()[deep] = self.row[vertical_idx];
-            if self.dance_internal(deep + 1) {
-                return true;
+        min
+    }
+
+    // Drive the search to the next solution with an explicit stack instead of recursion.
+    // If resume is true, frames hold the last solution and we backtrack from it first.
+    // Return false once the whole search tree is visited.
+    fn search_next(&mut self, frames: &mut Vec<Frame>, resume: bool) -> bool {
+        let mut descend = !resume;
+        loop {
+            if descend {
+                self.stats.enter(frames.len());
+                // if empty, we find a solution
+                if self.R[0] == 0 {
+                    return true;
+                }
+                let min = self.min_column();
+                self.stats.branch(frames.len(), self.size[min] as u64);
+                self.remove(min);
+                frames.push(Frame {
+                    col: min,
+                    node: min,
+                });
+            } else if frames.is_empty() {
+                return false;
             }
-            vertical_idx = self.D[vertical_idx]
+            let frame = frames.last_mut().unwrap();
+            frame.node = self.D[frame.node];
+            if frame.node == frame.col {
+                // All rows in this column have been tried, go back to the upper level
+                let col = frame.col;
+                frames.pop();
+                self.recover(col);
+                self.stats.backtracks += 1;
+                descend = false;
+                continue;
+            }
+            descend = true;
         }
-        self.recover(min);
+    }
 
-        false
+    // Undo all the frames left on the stack, so the matrix is the same as before the search
+    fn unwind(&mut self, frames: &mut Vec<Frame>) {
+        while let Some(frame) = frames.pop() {
+            self.recover(frame.col);
+        }
     }
 
-    pub fn dance(&mut self) -> Result<Vec<usize>, String> {
+    pub fn dance(&mut self) -> Result<Vec<usize>, DlxError> {
         tracing::info!("Into dancing");
-        self.res = Some(vec![0; MAX_DEEP]);
-        let res = self.dance_internal(0);
-        if !res {
-            Err("This is a useless info to make clippy happy".to_string())
+        self.stats = SearchStats::default();
+        let mut frames = vec![];
+        let res = if self.search_next(&mut frames, false) {
+            Ok(frames.iter().map(|f| self.row[f.node]).collect())
         } else {
-            self.res
-                .clone()
-                .ok_or("This is a useless info to make clippy happy".to_string())
+            Err(DlxError::NoSolution)
+        };
+        self.unwind(&mut frames);
+        res
+    }
+
+    // Same as dance, with the counters of the search
+    pub fn dance_with_stats(&mut self) -> (Result<Vec<usize>, DlxError>, SearchStats) {
+        let res = self.dance();
+        (res, self.stats.clone())
+    }
+
+    // Counters of the last search, dance or min_cover
+    pub fn stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    // Unlink all the other elements in the column of node from their rows, the header included.
+    // Once a row is chosen, the columns it covers need nothing more from the other rows.
+    // Code Reference: remove in cpp/src/dancing_link.cpp
+    fn remove_cover(&mut self, node: usize) {
+        let mut vertical_idx = self.D[node];
+        while vertical_idx != node {
+            self.L[self.R[vertical_idx]] = self.L[vertical_idx];
+            self.R[self.L[vertical_idx]] = self.R[vertical_idx];
+            self.stats.updates += 1;
+            vertical_idx = self.D[vertical_idx];
+        }
+    }
+
+    fn recover_cover(&mut self, node: usize) {
+        let mut vertical_idx = self.U[node];
+        while vertical_idx != node {
+            self.L[self.R[vertical_idx]] = vertical_idx;
+            self.R[self.L[vertical_idx]] = vertical_idx;
+            self.stats.updates += 1;
+            vertical_idx = self.U[vertical_idx];
+        }
+    }
+
+    // Choose the row of node, every column in it is covered
+    fn select_cover(&mut self, node: usize) {
+        self.remove_cover(node);
+        let mut horizontal_idx = self.R[node];
+        while horizontal_idx != node {
+            self.remove_cover(horizontal_idx);
+            horizontal_idx = self.R[horizontal_idx];
+        }
+    }
+
+    fn unselect_cover(&mut self, node: usize) {
+        let mut horizontal_idx = self.L[node];
+        while horizontal_idx != node {
+            self.recover_cover(horizontal_idx);
+            horizontal_idx = self.L[horizontal_idx];
         }
+        self.recover_cover(node);
+    }
+
+    // A lower bound of rows we still need: take an uncovered column, count 1
+    // and pretend every column sharing a row with it is covered too, until no column is left.
+    // Code Reference: f in cpp/src/dancing_link.cpp
+    fn lower_bound(&self) -> usize {
+        let mut uncovered = vec![false; self.c + 1];
+        let mut horizontal_idx = self.R[0];
+        while horizontal_idx != 0 {
+            uncovered[horizontal_idx] = true;
+            horizontal_idx = self.R[horizontal_idx];
+        }
+        let mut res = 0;
+        let mut col = self.R[0];
+        while col != 0 {
+            if uncovered[col] {
+                res += 1;
+                uncovered[col] = false;
+                let mut vertical_idx = self.D[col];
+                while vertical_idx != col {
+                    let mut horizontal_idx = self.R[vertical_idx];
+                    while horizontal_idx != vertical_idx {
+                        uncovered[self.col[horizontal_idx]] = false;
+                        horizontal_idx = self.R[horizontal_idx];
+                    }
+                    vertical_idx = self.D[vertical_idx];
+                }
+            }
+            col = self.R[col];
+        }
+        res
+    }
+
+    // Find a multicover with the least rows by branch and bound:
+    // a branch is cut once its depth plus lower_bound can not beat the best cover so far.
+    pub fn min_cover(&mut self) -> Result<Vec<usize>, DlxError> {
+        tracing::info!("Into min cover");
+        self.stats = SearchStats::default();
+        let mut frames: Vec<Frame> = vec![];
+        let mut best: Option<Vec<usize>> = None;
+        let mut descend = true;
+        loop {
+            if descend {
+                self.stats.enter(frames.len());
+                let best_len = best.as_ref().map_or(usize::MAX, |best| best.len());
+                if self.R[0] == 0 {
+                    // It must be better than best, or it would have been cut
+                    best = Some(frames.iter().map(|f| self.row[f.node]).collect());
+                } else if frames.len() + self.lower_bound() < best_len {
+                    let min = self.min_column();
+                    self.stats.branch(frames.len(), self.size[min] as u64);
+                    frames.push(Frame {
+                        col: min,
+                        node: min,
+                    });
+                }
+            }
+            let Some(frame) = frames.last_mut() else {
+                break;
+            };
+            if frame.node != frame.col {
+                self.unselect_cover(frame.node);
+            }
+            frame.node = self.D[frame.node];
+            if frame.node == frame.col {
+                frames.pop();
+                self.stats.backtracks += 1;
+                descend = false;
+                continue;
+            }
+            self.select_cover(frame.node);
+            descend = true;
+        }
+        best.ok_or(DlxError::NoSolution)
+    }
+}
+
+// The matrix must hold only 0 and 1, the cols are the cols of the first row
+impl TryFrom<&Matrix> for DlMulti {
+    type Error = DlxError;
+
+    fn try_from(matrix: &Matrix) -> Result<Self, Self::Error> {
+        let (col_size, rows) = utils::dense_rows(&matrix.0)?;
+        Self::with_rows(col_size, &rows)
     }
 }
 
+impl CoverSolver for DlMulti {
+    fn from_rows(col_size: usize, rows: &[Vec<usize>]) -> Result<Self, DlxError> {
+        Self::with_rows(col_size, rows)
+    }
+
+    // dance picks a row for each col, so a row covering several cols comes up more than once
+    fn solve(&mut self) -> Result<Vec<usize>, DlxError> {
+        let mut res = self.dance()?;
+        let mut seen = vec![false; self.r + 1];
+        res.retain(|&row| !std::mem::replace(&mut seen[row], true));
+        Ok(res)
+    }
+
+    // The search only picks a row per col, it can not tell the distinct covers apart
+    fn solve_all(&mut self) -> Result<Vec<Vec<usize>>, DlxError> {
+        Err(DlxError::Unsupported(
+            "DlMulti can not enumerate distinct covers".to_string(),
+        ))
+    }
+
+    fn count(&mut self, _limit: Option<u64>) -> Result<u64, DlxError> {
+        Err(DlxError::Unsupported(
+            "DlMulti can not count distinct covers".to_string(),
+        ))
+    }
+
+    fn search_stats(&self) -> Option<&SearchStats> {
+        Some(self.stats())
+    }
+}
+
+// One level of the search: the chosen column and the row node we are trying in it.
+// node == col means no row is being tried.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    col: usize,
+    node: usize,
+}
+
 #[cfg(test)]
 mod test {
     const MAX_DL_TEST: usize = 10000;
@@ -219,7 +518,7 @@ mod test {
         let test_vec = vec![(1, 2), (2, 5), (3, 8), (30, 30), (25, 1), (30, 1)];
         println!("{dl}");
         for (row, col) in test_vec {
-            dl.insert(row, col);
+            dl.insert(row, col).unwrap();
         }
         println!("{dl}");
     }
@@ -227,35 +526,126 @@ mod test {
     #[test]
     fn temp_test() {
         let mut dl = DlMulti::new(4, 4);
-        dl.insert(1, 1);
-        dl.insert(2, 2);
-        dl.insert(3, 3);
-        dl.insert(4, 4);
+        dl.insert(1, 1).unwrap();
+        dl.insert(2, 2).unwrap();
+        dl.insert(3, 3).unwrap();
+        dl.insert(4, 4).unwrap();
         println!("{dl}");
         let res = dl.dance().unwrap();
         println!("{:?}", res);
     }
 
-    fn test_base(r: usize, c: usize, case: Vec<Vec<usize>>, cod: bool) -> bool {
-        let mut dl = DlMulti::new(r, c);
-        for (r_in, c_vec) in case.iter().enumerate() {
-            for (c_in, item) in c_vec.iter().enumerate() {
-                if *item == 1 {
-                    dl.insert(r_in + 1, c_in + 1)
+    #[test]
+    fn test_deep_solution() {
+        // Every column has its own row, so the solution needs all of them
+        let n = 1000;
+        let mut dl = DlMulti::new(n, n);
+        for i in 1..=n {
+            dl.insert(i, i).unwrap();
+        }
+        let mut sol = dl.dance().unwrap();
+        sol.sort();
+        assert_eq!(sol, (1..=n).collect::<Vec<_>>());
+        // The header row is restored, so we can dance again
+        assert_eq!(dl.dance().unwrap().len(), n);
+    }
+
+    #[test]
+    fn test_sparse_nodes() {
+        let (r, c) = (50_000, 2_000);
+        let mut dl = DlMulti::with_capacity(r, c, 3 * r);
+        for i in 1..=r {
+            for k in 0..3 {
+                dl.insert(i, (i * 3 + k) % c + 1).unwrap();
+            }
+        }
+        assert_eq!(dl.L.len(), c + 1 + 3 * r);
+        assert!(dl.dance().is_ok());
+    }
+
+    #[test]
+    fn test_stats() {
+        let n = 5;
+        let mut dl = DlMulti::new(n, n);
+        for i in 1..=n {
+            dl.insert(i, i).unwrap();
+        }
+        let (res, stats) = dl.dance_with_stats();
+        assert_eq!(res.unwrap().len(), n);
+        assert_eq!(stats.nodes, n as u64 + 1);
+        assert_eq!(stats.max_depth, n);
+        assert_eq!(stats.profile, vec![1; n + 1]);
+        assert_eq!(stats.branching, vec![1; n]);
+        assert_eq!(stats.backtracks, 0);
+
+        dl.min_cover().unwrap();
+        let stats = dl.stats();
+        assert_eq!(stats.nodes, stats.profile.iter().sum::<u64>());
+        assert_eq!(stats.max_depth, n);
+        assert!(stats.updates > 0 && stats.updates.is_multiple_of(2));
+    }
+
+    #[test]
+    fn test_min_cover() {
+        // Row 5 alone covers everything, but dance stops at the first cover it meets
+        let mut dl = DlMulti::new(5, 4);
+        let rows = [
+            vec![1, 2],
+            vec![3, 4],
+            vec![1, 3],
+            vec![2, 4],
+            vec![1, 2, 3, 4],
+        ];
+        for (r, cols) in rows.iter().enumerate() {
+            for &c in cols {
+                dl.insert(r + 1, c).unwrap();
+            }
+        }
+        assert_eq!(dl.min_cover().unwrap(), vec![5]);
+        // The matrix is restored
+        assert_eq!(dl.min_cover().unwrap(), vec![5]);
+        assert!(dl.dance().is_ok());
+
+        let mut dl = DlMulti::new(2, 2);
+        dl.insert(1, 1).unwrap();
+        dl.insert(2, 1).unwrap();
+        assert_eq!(dl.min_cover(), Err(DlxError::NoSolution));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(200))]
+        #[test]
+        fn test_min_cover_brute_force(case in test_utils::matrix_strategy(1..=10, 1..=8)) {
+            let (r, c) = (case.len(), case[0].len());
+            let mut dl = DlMulti::try_from(&Matrix(case.clone())).unwrap();
+            let expected = (0u64..(1 << r))
+                .filter(|mask| {
+                    (0..c).all(|col| (0..r).any(|row| mask & (1 << row) != 0 && case[row][col] == 1))
+                })
+                .map(|mask| mask.count_ones() as usize)
+                .min();
+            match dl.min_cover() {
+                Ok(sol) => {
+                    prop_assert_eq!(Some(sol.len()), expected);
+                    let sol = sol.iter().map(|row| case[row - 1].clone()).collect();
+                    prop_assert!(test_utils::check_multicover(sol, false));
+                }
+                Err(err) => {
+                    prop_assert_eq!(err, DlxError::NoSolution);
+                    prop_assert_eq!(expected, None);
                 }
             }
         }
+    }
+
+    fn test_base(case: Vec<Vec<usize>>, cod: bool) -> bool {
+        let mut dl = DlMulti::try_from(&Matrix(case.clone())).unwrap();
         println_cod!(cod, "array:\n {dl}");
         println_cod!(cod, "array size: {:?}", dl.size);
         let res = dl.dance();
         println_cod!(cod, "dancing res: {:?}", res);
         if let Ok(sol) = &res {
-            let sol = sol
-                .iter()
-                .enumerate()
-                .filter(|(_, &x)| x != 0)
-                .map(|(idx, _)| case[sol[idx] - 1].clone())
-                .collect();
+            let sol = sol.iter().map(|row| case[row - 1].clone()).collect();
             println_cod!(cod, "solution: {:?}", sol);
             if test_utils::check_multicover(sol, cod) {
                 return true;
@@ -284,7 +674,7 @@ mod test {
 
         // Spawn a new thread to run the test logic
         let handle = thread::spawn(move || {
-            let res = test_base(r, c, case_clone, DEBUG_MODE);
+            let res = test_base(case_clone, DEBUG_MODE);
             let _ = tx.send(());
             res
         });
@@ -1,59 +1,160 @@
-use std::fmt::Display;
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
 
-use crate::utils;
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chooser::{ColumnChooser, Mrv},
+    error::DlxError,
+    job::Job,
+    limits::{Limiter, SearchLimits, SearchStatus},
+    nodes::{Nodes, SoaNodes},
+    observer::{NoopObserver, SearchObserver},
+    solver::CoverSolver,
+    stats::SearchStats,
+    utils::{self, Matrix},
+};
 
 // Code Reference: http://magic.vicp.io/oi-wiki/search/dlx/
 // Code Reference: https://blog.csdn.net/nameofcsdn/article/details/132225150
 
-#[allow(non_snake_case)]
+// The links, cols, rows and colors of the nodes live in N, see nodes.rs for the layouts.
+// DL::new and the other constructors use SoaNodes<usize>,
+// e.g. DL::<AosNodes<u32>>::with_nodes picks the interleaved layout with u32 indices.
+// C chooses the column to branch on, Mrv unless with_chooser gives another one.
 #[derive(Debug, Clone)]
-pub struct DL {
+pub struct DL<N: Nodes = SoaNodes<usize>, C: ColumnChooser = Mrv> {
     r: usize, // row size
     c: usize, // col size
+    // Whether each col must be covered, the others are secondary.
+    // Cols 1..=primary of with_secondary are primary, add_column appends more.
+    primary: Vec<bool>,
+    // Cols given up by retire_column, they have no elements and can not get any
+    retired: Vec<bool>,
     idx: usize,
     first: Vec<usize>,
+    weight: Vec<u64>, // cost of each row, only dance_min_cost cares about it
     size: Vec<isize>,
-    row: Vec<usize>,
-    col: Vec<usize>,
-    L: Vec<usize>,
-    R: Vec<usize>,
-    U: Vec<usize>,
-    D: Vec<usize>,
-    res: Option<Vec<usize>>, // usize: the max deep when dancing
+    // Besides the links, a node keeps its row, its color and its seq.
+    // Colors are those of the elements in secondary cols, 0 means no color.
+    // It is set to PURIFIED when the element is known to agree with the color chosen for its col.
+    // Seq is the order the elements were inserted in, a node keeps it when add_column moves it.
+    nodes: N,
+    stats: SearchStats, // counters of the last search
+    chooser: C,
+    // Rows forced by select_row, they are covered before any search and start every solution
+    selected: Vec<Frame>,
+    // Rows taken out by remove_row, their nodes are out of the cols until restore_row
+    removed: Vec<bool>,
 }
 
 const DEFAULT_ROW: usize = 10;
 const DEFAULT_COL: usize = 10;
-const MAX_DEEP: usize = 500;
 
 impl DL {
-    #[allow(non_snake_case)]
     pub fn new(row_size: usize, col_size: usize) -> Self {
         let row_size = if row_size < 1 { DEFAULT_ROW } else { row_size };
         let col_size = if col_size < 1 { DEFAULT_COL } else { col_size };
-        let idx_max = row_size * col_size + 1;
+        Self::with_secondary(row_size, col_size, 0)
+    }
+
+    // Same as new, but the nodes of nonzeros elements are allocated at once
+    pub fn with_capacity(row_size: usize, col_size: usize, nonzeros: usize) -> Self {
+        let mut dl = Self::new(row_size, col_size);
+        dl.reserve(nonzeros);
+        dl
+    }
+
+    // Cols 1..=primary must be covered exactly once, as in new.
+    // Cols primary + 1..=primary + secondary are secondary: they may be covered at most once,
+    // so they are never chosen when dancing and a solution does not need them.
+    pub fn with_secondary(row_size: usize, primary: usize, secondary: usize) -> Self {
+        Self::with_nodes(row_size, primary, secondary)
+    }
+
+    // All cols are primary and the i-th item of rows lists the cols of row i + 1,
+    // e.g. from_rows(3, [vec![1, 3], vec![2]])
+    pub fn from_rows<R: IntoIterator<Item = usize>>(
+        col_size: usize,
+        rows: impl IntoIterator<Item = R>,
+    ) -> Result<Self, DlxError> {
+        let rows: Vec<Vec<usize>> = rows.into_iter().map(|r| r.into_iter().collect()).collect();
+        Self::with_rows(col_size, &rows)
+    }
+
+    // The cols of the matrix are the cols of the first row
+    pub fn from_dense(matrix: &[Vec<bool>]) -> Result<Self, DlxError> {
+        let (col_size, rows) = utils::dense_rows(matrix)?;
+        Self::with_rows(col_size, &rows)
+    }
+
+    // A matrix in CSR form: the ones of row i + 1 are in the cols indices[indptr[i]..indptr[i + 1]],
+    // which start with 0 as in scipy.sparse.csr_matrix
+    pub fn from_sparse(
+        col_size: usize,
+        indptr: &[usize],
+        indices: &[usize],
+    ) -> Result<Self, DlxError> {
+        let rows = utils::sparse_rows(col_size, indptr, indices)?;
+        Self::with_rows(col_size, &rows)
+    }
+}
+
+impl<N: Nodes> DL<N> {
+    // A matrix of only primary cols, rows[i] lists the cols of row i + 1
+    fn with_rows(col_size: usize, rows: &[Vec<usize>]) -> Result<Self, DlxError> {
+        let mut dl = Self::with_nodes(rows.len(), col_size, 0);
+        dl.reserve(rows.iter().map(Vec::len).sum());
+        for (r_in, cols) in rows.iter().enumerate() {
+            for &col in cols {
+                dl.insert(r_in + 1, col)?;
+            }
+        }
+        Ok(dl)
+    }
+
+    // Same as with_secondary, but the nodes are stored in N.
+    // Panics if the header nodes do not fit in the index type of N.
+    pub fn with_nodes(row_size: usize, primary: usize, secondary: usize) -> Self {
+        let col_size = primary + secondary;
+        // Only the virtual row is allocated here, the other nodes are pushed when inserting
+        let idx_max = col_size + 1;
+        assert!(
+            idx_max <= N::MAX_LEN,
+            "{col_size} cols do not fit in the node index type"
+        );
         // Actually, we do not use idx 0, so idx in first and size starts with 1
         let first = vec![0; row_size + 1];
+        let weight = vec![0; row_size + 1];
+        let removed = vec![false; row_size + 1];
         let size = vec![0; col_size + 1];
-        let row = vec![0; idx_max];
-        let col = vec![0; idx_max];
-        let mut L = vec![0; idx_max];
-        let mut R = vec![0; idx_max];
-        let mut U = vec![0; idx_max];
-        let mut D = vec![0; idx_max];
-        let res = None;
+        let is_primary = (0..=col_size).map(|i| i != 0 && i <= primary).collect();
+        let retired = vec![false; col_size + 1];
+        let mut nodes = N::default();
+        nodes.reserve(idx_max);
 
         // We build a new virtual row, but we don't add them to row or col vectors
         // Note: Extra idx 0 element
         for i in 0..=col_size {
+            nodes.push();
             // In virtual row, we link each element to itself vertically
-            D[i] = i;
-            U[i] = i;
-            // In the horizontal direction, the elements in the virtual row link to each other
-            // ->0->1->...->col_size->
-            // <-0->1<-...<-col_size<-
-            L[i] = if i != 0 { i - 1 } else { col_size };
-            R[i] = if i != col_size { i + 1 } else { 0 };
+            nodes.set_d(i, i);
+            nodes.set_u(i, i);
+            // In the horizontal direction, the primary elements in the virtual row link to each other
+            // ->0->1->...->primary->
+            // <-0->1<-...<-primary<-
+            // Secondary elements only link to themselves, so they are out of the header ring
+            if i <= primary {
+                nodes.set_l(i, if i != 0 { i - 1 } else { primary });
+                nodes.set_r(i, if i != primary { i + 1 } else { 0 });
+            } else {
+                nodes.set_l(i, i);
+                nodes.set_r(i, i);
+            }
         }
 
         // We maintain a global idx and it is in ascending order
@@ -63,61 +164,461 @@ impl DL {
         DL {
             r: row_size,
             c: col_size,
+            primary: is_primary,
+            retired,
             idx,
             first,
+            weight,
             size,
-            row,
-            col,
-            L,
-            R,
-            U,
-            D,
-            res,
+            nodes,
+            stats: SearchStats::default(),
+            chooser: Mrv,
+            selected: vec![],
+            removed,
+        }
+    }
+}
+
+impl<N: Nodes, C: ColumnChooser> DL<N, C> {
+    // The color of an element whose col already has its color, see purify
+    const PURIFIED: usize = N::MAX_VALUE;
+
+    // Branch on the columns picked by chooser instead
+    pub fn with_chooser<C2: ColumnChooser>(self, chooser: C2) -> DL<N, C2> {
+        DL {
+            r: self.r,
+            c: self.c,
+            primary: self.primary,
+            retired: self.retired,
+            idx: self.idx,
+            first: self.first,
+            weight: self.weight,
+            size: self.size,
+            nodes: self.nodes,
+            stats: self.stats,
+            chooser,
+            selected: self.selected,
+            removed: self.removed,
         }
     }
 
     // row and col idx starts with 1
-    pub fn insert(&mut self, row: usize, col: usize) {
-        assert!(
-            row <= self.r && col <= self.c,
-            "Insert: row or col is out of index"
-        );
+    pub fn insert(&mut self, row: usize, col: usize) -> Result<(), DlxError> {
+        self.insert_colored(row, col, 0)
+    }
+
+    // Insert an element with a color into a secondary col.
+    // Two rows can share a secondary col if they give it the same color (color 0 means no color).
+    pub fn insert_colored(&mut self, row: usize, col: usize, color: usize) -> Result<(), DlxError> {
+        if row == 0 || row > self.r || col == 0 || col > self.c {
+            return Err(DlxError::InvalidInput(format!(
+                "insert ({row}, {col}) is out of index, rows are 1..={} and cols are 1..={}",
+                self.r, self.c
+            )));
+        }
+        if self.retired[col] {
+            return Err(DlxError::InvalidInput(format!(
+                "insert ({row}, {col}), but col {col} is retired"
+            )));
+        }
+        if self.contains(row, col) {
+            return Err(DlxError::InvalidInput(format!(
+                "insert ({row}, {col}) twice"
+            )));
+        }
+        if self.removed[row] {
+            return Err(DlxError::InvalidInput(format!(
+                "insert ({row}, {col}), but row {row} is removed"
+            )));
+        }
+        // Selected rows cover cols and hide rows, linking into them breaks the matrix
+        if !self.selected.is_empty() {
+            return Err(DlxError::InvalidInput(format!(
+                "insert ({row}, {col}), but the matrix can not be edited while rows are selected"
+            )));
+        }
+        if color != 0 && self.primary[col] {
+            return Err(DlxError::InvalidInput(format!(
+                "insert ({row}, {col}) with color {color}, but only secondary cols can have colors"
+            )));
+        }
+        // The largest value of the node index type marks purified elements
+        if color >= Self::PURIFIED {
+            return Err(DlxError::InvalidInput(format!(
+                "insert ({row}, {col}) with color {color}, but colors must be below {}",
+                Self::PURIFIED
+            )));
+        }
+        if row > N::MAX_VALUE {
+            return Err(DlxError::InvalidInput(format!(
+                "insert ({row}, {col}), but the node index type holds rows up to {}",
+                N::MAX_VALUE
+            )));
+        }
+        if self.idx + 1 >= N::MAX_LEN {
+            return Err(DlxError::InvalidInput(format!(
+                "insert ({row}, {col}), but there are already {} nodes, the most the node index type can hold",
+                self.idx + 1
+            )));
+        }
         // Because of an extra 0 idx, when we insert a elem, we should ++idx first
         self.idx += 1;
-        self.row[self.idx] = row;
-        self.col[self.idx] = col;
+        self.push_node();
+        let idx = self.idx;
+        self.nodes.set_row(idx, row);
+        self.nodes.set_col(idx, col);
+        self.nodes.set_color(idx, color);
+        // Nodes are never freed, so the newest node has the largest idx
+        self.nodes.set_seq(idx, idx);
         self.size[col] += 1;
         // Idx directly links to col element in virtual row
         // Like head insert in linked list
-        self.U[self.idx] = col;
-        self.D[self.idx] = self.D[col];
-        self.U[self.D[col]] = self.idx;
-        self.D[col] = self.idx;
+        self.nodes.set_u(idx, col);
+        self.nodes.set_d(idx, self.nodes.d(col));
+        self.nodes.set_u(self.nodes.d(col), idx);
+        self.nodes.set_d(col, idx);
         // Condition 1
         // There is no element in row r, we directly insert it into this row
         // and let the point first point to this element
-        if self.first[row] == 0 {
-            self.first[row] = self.idx;
-            self.L[self.idx] = self.idx;
-            self.R[self.idx] = self.idx;
+        let first = self.first[row];
+        if first == 0 {
+            self.first[row] = idx;
+            self.nodes.set_l(idx, idx);
+            self.nodes.set_r(idx, idx);
         } else {
             // Once the first[row] is not 0, it can't be change when inserting
             // So, we insert a new element after element the first[row] points to
-            self.L[self.idx] = self.first[row];
-            self.R[self.idx] = self.R[self.first[row]];
-            self.L[self.R[self.first[row]]] = self.idx;
-            self.R[self.first[row]] = self.idx;
+            self.nodes.set_l(idx, first);
+            self.nodes.set_r(idx, self.nodes.r(first));
+            self.nodes.set_l(self.nodes.r(first), idx);
+            self.nodes.set_r(first, idx);
+        }
+        Ok(())
+    }
+
+    // Set the cost of choosing row, rows cost 0 by default
+    pub fn set_weight(&mut self, row: usize, weight: u64) -> Result<(), DlxError> {
+        if row == 0 || row > self.r {
+            return Err(DlxError::InvalidInput(format!(
+                "set weight of row {row}, but rows are 1..={}",
+                self.r
+            )));
+        }
+        self.weight[row] = weight;
+        Ok(())
+    }
+
+    // Insert an element and set the weight of its row at the same time
+    pub fn insert_weighted(&mut self, row: usize, col: usize, weight: u64) -> Result<(), DlxError> {
+        self.insert(row, col)?;
+        self.weight[row] = weight;
+        Ok(())
+    }
+
+    // Take row out of the matrix, as if it had never been inserted, until restore_row.
+    // The matrix can only be edited while no rows are selected.
+    pub fn remove_row(&mut self, row: usize) -> Result<(), DlxError> {
+        self.check_editable(row)?;
+        if self.removed[row] {
+            return Err(DlxError::InvalidInput(format!(
+                "row {row} is already removed"
+            )));
+        }
+        self.removed[row] = true;
+        let first_idx = self.first[row];
+        let mut idx = first_idx;
+        while idx != 0 {
+            let (u, d) = (self.nodes.u(idx), self.nodes.d(idx));
+            self.nodes.set_u(d, u);
+            self.nodes.set_d(u, d);
+            self.size[self.nodes.col(idx)] -= 1;
+            idx = self.nodes.r(idx);
+            if idx == first_idx {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // Put a row taken out by remove_row back.
+    // Rows can be restored in any order, so the place of each node in its col is searched
+    // again: the nodes of a col are kept from the newest to the oldest by seq, as insert links them.
+    pub fn restore_row(&mut self, row: usize) -> Result<(), DlxError> {
+        self.check_editable(row)?;
+        if !self.removed[row] {
+            return Err(DlxError::InvalidInput(format!("row {row} is not removed")));
+        }
+        self.removed[row] = false;
+        let first_idx = self.first[row];
+        let mut idx = first_idx;
+        while idx != 0 {
+            let col = self.nodes.col(idx);
+            let mut below = self.nodes.d(col);
+            while below != col && self.nodes.seq(below) > self.nodes.seq(idx) {
+                below = self.nodes.d(below);
+            }
+            let above = self.nodes.u(below);
+            self.nodes.set_u(idx, above);
+            self.nodes.set_d(idx, below);
+            self.nodes.set_d(above, idx);
+            self.nodes.set_u(below, idx);
+            self.size[col] += 1;
+            idx = self.nodes.r(idx);
+            if idx == first_idx {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // Append a new row with an element in each of cols and return its id.
+    // Nothing is added if a col is out of index or given twice.
+    pub fn add_row(&mut self, cols: &[usize]) -> Result<usize, DlxError> {
+        if !self.selected.is_empty() {
+            return Err(DlxError::InvalidInput(
+                "add a row, but the matrix can not be edited while rows are selected".to_string(),
+            ));
+        }
+        for (i, &col) in cols.iter().enumerate() {
+            if col == 0 || col > self.c || self.retired[col] {
+                return Err(DlxError::InvalidInput(format!(
+                    "add a row with col {col}, but it is not one of the cols 1..={} left",
+                    self.c
+                )));
+            }
+            if cols[..i].contains(&col) {
+                return Err(DlxError::InvalidInput(format!(
+                    "add a row with col {col} twice"
+                )));
+            }
+        }
+        if self.idx + cols.len() >= N::MAX_LEN {
+            return Err(DlxError::InvalidInput(format!(
+                "add a row of {} elements, but there are already {} nodes, the most the node index type can hold",
+                cols.len(),
+                self.idx + 1
+            )));
+        }
+        self.r += 1;
+        self.first.push(0);
+        self.weight.push(0);
+        self.removed.push(false);
+        for &col in cols {
+            self.insert(self.r, col)?;
+        }
+        Ok(self.r)
+    }
+
+    // Append a primary col with no elements and return its id
+    pub fn add_column(&mut self) -> Result<usize, DlxError> {
+        self.push_column(true)
+    }
+
+    // Append a secondary col with no elements and return its id
+    pub fn add_secondary_column(&mut self) -> Result<usize, DlxError> {
+        self.push_column(false)
+    }
+
+    // The header of col c + 1 takes node c + 1, so the element there is moved to the end first
+    fn push_column(&mut self, primary: bool) -> Result<usize, DlxError> {
+        if !self.selected.is_empty() {
+            return Err(DlxError::InvalidInput(
+                "add a col, but the matrix can not be edited while rows are selected".to_string(),
+            ));
+        }
+        if self.idx + 1 >= N::MAX_LEN {
+            return Err(DlxError::InvalidInput(format!(
+                "add a col, but there are already {} nodes, the most the node index type can hold",
+                self.idx + 1
+            )));
+        }
+        let col = self.c + 1;
+        self.push_node();
+        if self.idx >= col {
+            self.move_node(col, self.idx + 1);
+        }
+        self.idx += 1;
+        self.c = col;
+        self.nodes.set_row(col, 0);
+        self.nodes.set_color(col, 0);
+        self.nodes.set_col(col, 0);
+        self.nodes.set_u(col, col);
+        self.nodes.set_d(col, col);
+        if primary {
+            // The last one in the header list
+            let last = self.nodes.l(0);
+            self.nodes.set_l(col, last);
+            self.nodes.set_r(col, 0);
+            self.nodes.set_r(last, col);
+            self.nodes.set_l(0, col);
+        } else {
+            self.nodes.set_l(col, col);
+            self.nodes.set_r(col, col);
+        }
+        self.size.push(0);
+        self.primary.push(primary);
+        self.retired.push(false);
+        Ok(col)
+    }
+
+    // Move the element at node from to the unused node to, the links to it follow
+    fn move_node(&mut self, from: usize, to: usize) {
+        let (l, r, u, d) = (
+            self.nodes.l(from),
+            self.nodes.r(from),
+            self.nodes.u(from),
+            self.nodes.d(from),
+        );
+        let row = self.nodes.row(from);
+        self.nodes.set_row(to, row);
+        self.nodes.set_color(to, self.nodes.color(from));
+        self.nodes.set_seq(to, self.nodes.seq(from));
+        self.nodes.set_col(to, self.nodes.col(from));
+        if self.nodes.col(from) == 0 {
+            // Detached by retire_column, nothing links to it
+            self.nodes.set_l(to, to);
+            self.nodes.set_r(to, to);
+            self.nodes.set_u(to, to);
+            self.nodes.set_d(to, to);
+            return;
+        }
+        if l == from {
+            // The only element of its row
+            self.nodes.set_l(to, to);
+            self.nodes.set_r(to, to);
+        } else {
+            self.nodes.set_l(to, l);
+            self.nodes.set_r(to, r);
+            self.nodes.set_r(l, to);
+            self.nodes.set_l(r, to);
+        }
+        self.nodes.set_u(to, u);
+        self.nodes.set_d(to, d);
+        // A removed row is out of its cols, restore_row links it again
+        if !self.removed[row] {
+            self.nodes.set_d(u, to);
+            self.nodes.set_u(d, to);
+        }
+        if self.first[row] == from {
+            self.first[row] = to;
+        }
+    }
+
+    // Give up col: it is taken out of the header list and its elements out of their rows,
+    // so it no longer constrains anything. Its id is not used again.
+    pub fn retire_column(&mut self, col: usize) -> Result<(), DlxError> {
+        if col == 0 || col > self.c || self.retired[col] {
+            return Err(DlxError::InvalidInput(format!(
+                "retire col {col}, but it is not one of the cols 1..={} left",
+                self.c
+            )));
+        }
+        if !self.selected.is_empty() {
+            return Err(DlxError::InvalidInput(format!(
+                "retire col {col}, but the matrix can not be edited while rows are selected"
+            )));
+        }
+        // The elements of removed rows are not in the col, so the rows are walked instead
+        for row in 1..=self.r {
+            let first_idx = self.first[row];
+            let mut idx = first_idx;
+            while idx != 0 {
+                let next = self.nodes.r(idx);
+                if self.nodes.col(idx) == col {
+                    let l = self.nodes.l(idx);
+                    self.nodes.set_r(l, next);
+                    self.nodes.set_l(next, l);
+                    if idx == first_idx {
+                        self.first[row] = if next == idx { 0 } else { next };
+                    }
+                    // Detach the node, so a later move_node does not link it back
+                    self.nodes.set_col(idx, 0);
+                    self.nodes.set_l(idx, idx);
+                    self.nodes.set_r(idx, idx);
+                    self.nodes.set_u(idx, idx);
+                    self.nodes.set_d(idx, idx);
+                    break;
+                }
+                idx = next;
+                if idx == first_idx {
+                    break;
+                }
+            }
+        }
+        let (l, r) = (self.nodes.l(col), self.nodes.r(col));
+        self.nodes.set_r(l, r);
+        self.nodes.set_l(r, l);
+        self.nodes.set_l(col, col);
+        self.nodes.set_r(col, col);
+        self.nodes.set_u(col, col);
+        self.nodes.set_d(col, col);
+        self.size[col] = 0;
+        self.primary[col] = false;
+        self.retired[col] = true;
+        Ok(())
+    }
+
+    // Whether row can be removed or restored now
+    fn check_editable(&self, row: usize) -> Result<(), DlxError> {
+        if row == 0 || row > self.r {
+            return Err(DlxError::InvalidInput(format!(
+                "row {row} is out of index, rows are 1..={}",
+                self.r
+            )));
+        }
+        if !self.selected.is_empty() {
+            return Err(DlxError::InvalidInput(format!(
+                "edit row {row}, but the matrix can not be edited while rows are selected"
+            )));
+        }
+        Ok(())
+    }
+
+    // Reserve nodes for at least additional more elements,
+    // e.g. the number of ones in the matrix before inserting them
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+    }
+
+    // The bytes the nodes take on the heap, to compare the layouts of N
+    pub fn node_bytes(&self) -> usize {
+        self.nodes.heap_bytes()
+    }
+
+    // Allocate a node at the end, its fields are set by insert
+    fn push_node(&mut self) {
+        self.nodes.push();
+    }
+
+    // Whether there is already an element at (row, col)
+    fn contains(&self, row: usize, col: usize) -> bool {
+        let first_idx = self.first[row];
+        if first_idx == 0 {
+            return false;
+        }
+        let mut idx = first_idx;
+        loop {
+            if self.nodes.col(idx) == col {
+                return true;
+            }
+            idx = self.nodes.r(idx);
+            if idx == first_idx {
+                return false;
+            }
         }
     }
 
     fn get_2d_vec(&self) -> Vec<Vec<usize>> {
         let mut res_vec = vec![vec![0; self.c + 1]; self.r + 1];
         for (r, row) in res_vec.iter_mut().enumerate().take(self.r + 1).skip(1) {
+            if self.removed[r] {
+                continue;
+            }
             let first_idx = self.first[r];
             let mut idx = first_idx;
-            while idx != 0 && self.L[idx] != 0 {
-                row[self.col[idx]] = 1;
-                idx = self.R[idx];
+            while idx != 0 && self.nodes.l(idx) != 0 {
+                row[self.nodes.col(idx)] = 1;
+                idx = self.nodes.r(idx);
                 if idx == first_idx {
                     break;
                 }
@@ -127,227 +628,2293 @@ impl DL {
     }
 }
 
-// It is maybe only correct before any removal operation.
-impl Display for DL {
+// Rows taken out by remove_row are shown as empty rows.
+impl<N: Nodes, C: ColumnChooser> Display for DL<N, C> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let str_ = utils::format_2d_string(&self.get_2d_vec());
         write!(f, "{str_}")
     }
 }
 
-impl DL {
+// The matrix must hold only 0 and 1, the cols are the cols of the first row
+impl TryFrom<&Matrix> for DL {
+    type Error = DlxError;
+
+    fn try_from(matrix: &Matrix) -> Result<Self, Self::Error> {
+        let (col_size, rows) = utils::dense_rows(&matrix.0)?;
+        Self::with_rows(col_size, &rows)
+    }
+}
+
+impl<N: Nodes> CoverSolver for DL<N> {
+    // All the cols are primary
+    fn from_rows(col_size: usize, rows: &[Vec<usize>]) -> Result<Self, DlxError> {
+        Self::with_rows(col_size, rows)
+    }
+
+    fn solve(&mut self) -> Result<Vec<usize>, DlxError> {
+        self.dance()
+    }
+
+    fn solve_all(&mut self) -> Result<Vec<Vec<usize>>, DlxError> {
+        Ok(self.all_solutions())
+    }
+
+    fn count(&mut self, limit: Option<u64>) -> Result<u64, DlxError> {
+        Ok(self.count_solutions(limit))
+    }
+
+    fn search_stats(&self) -> Option<&SearchStats> {
+        Some(self.stats())
+    }
+}
+
+impl<N: Nodes, C: ColumnChooser> DL<N, C> {
     // In this function, we will remove the column and the corresponding rows to which items in this column are linked.
     fn remove(&mut self, col: usize) {
-        let mut vertical_idx = self.D[col];
-        let mut horizontal_idx;
-        self.L[self.R[col]] = self.L[col];
-        self.R[self.L[col]] = self.R[col];
+        let mut vertical_idx = self.nodes.d(col);
+        self.nodes.set_l(self.nodes.r(col), self.nodes.l(col));
+        self.nodes.set_r(self.nodes.l(col), self.nodes.r(col));
+        self.stats.updates += 1;
         while vertical_idx != col {
-            horizontal_idx = self.R[vertical_idx];
-            while horizontal_idx != vertical_idx {
-                self.U[self.D[horizontal_idx]] = self.U[horizontal_idx];
-                self.D[self.U[horizontal_idx]] = self.D[horizontal_idx];
-                self.size[self.col[horizontal_idx]] -= 1;
-                horizontal_idx = self.R[horizontal_idx];
-            }
-            vertical_idx = self.D[vertical_idx];
+            self.hide(vertical_idx);
+            vertical_idx = self.nodes.d(vertical_idx);
         }
     }
 
     // In this function, we will restore the column and the corresponding rows that were previously removed.
     fn recover(&mut self, col: usize) {
-        let mut vertical_idx = self.U[col];
-        let mut horizontal_idx;
+        let mut vertical_idx = self.nodes.u(col);
         while vertical_idx != col {
-            horizontal_idx = self.L[vertical_idx];
-            while horizontal_idx != vertical_idx {
-                self.U[self.D[horizontal_idx]] = horizontal_idx;
-                self.D[self.U[horizontal_idx]] = horizontal_idx;
-                self.size[self.col[horizontal_idx]] += 1;
-                horizontal_idx = self.L[horizontal_idx];
-            }
-            vertical_idx = self.U[vertical_idx];
-        }
-        self.L[self.R[col]] = col;
-        self.R[self.L[col]] = col;
-    }
-
-    #[tracing::instrument]
-    fn dance_internal(&mut self, deep: usize) -> bool {
-        tracing::info!("Into dancing internal");
-        // if empty, return false
-        if self.R[0] == 0 {
-            // In external function, we should ensure self.res is not None
-            if let Some(res_vec) = self.res.as_mut() {
-                res_vec.truncate(deep)
-            }
-            return true;
-        }
-        // Choose the column with least elements
-        let mut min = self.R[0];
-        let mut horizontal_idx = self.R[0];
-        while horizontal_idx != 0 {
-            let cur = self.size[horizontal_idx];
-            if cur < self.size[min] {
-                min = horizontal_idx;
-            }
-            horizontal_idx = self.R[horizontal_idx]
-        }
-        tracing::info!("Choose column: {}", min);
-        // Attemp to remove the selected column
-        self.remove(min);
-
-        let mut vertical_idx = self.D[min];
-        let mut horizontal_idx;
-        while vertical_idx != min {
-            self.res.as_mut().unwrap()[deep] = self.row[vertical_idx];
-            horizontal_idx = self.R[vertical_idx];
-            while horizontal_idx != vertical_idx {
-                self.remove(self.col[horizontal_idx]);
-                horizontal_idx = self.R[horizontal_idx];
-            }
-            if self.dance_internal(deep + 1) {
-                return true;
+            self.unhide(vertical_idx);
+            vertical_idx = self.nodes.u(vertical_idx);
+        }
+        self.nodes.set_l(self.nodes.r(col), col);
+        self.nodes.set_r(self.nodes.l(col), col);
+        self.stats.updates += 1;
+    }
+
+    // Unlink the other elements in the row of node from their cols.
+    // Elements with color PURIFIED are skipped, their cols are secondary and already purified.
+    fn hide(&mut self, node: usize) {
+        let mut horizontal_idx = self.nodes.r(node);
+        while horizontal_idx != node {
+            if self.nodes.color(horizontal_idx) != Self::PURIFIED {
+                let (u, d) = (self.nodes.u(horizontal_idx), self.nodes.d(horizontal_idx));
+                self.nodes.set_u(d, u);
+                self.nodes.set_d(u, d);
+                self.size[self.nodes.col(horizontal_idx)] -= 1;
+                self.stats.updates += 1;
             }
-            let mut h_idx_recover = self.L[vertical_idx];
-            while h_idx_recover != vertical_idx {
-                self.recover(self.col[h_idx_recover]);
-                h_idx_recover = self.L[h_idx_recover];
+            horizontal_idx = self.nodes.r(horizontal_idx);
+        }
+    }
+
+    fn unhide(&mut self, node: usize) {
+        let mut horizontal_idx = self.nodes.l(node);
+        while horizontal_idx != node {
+            if self.nodes.color(horizontal_idx) != Self::PURIFIED {
+                let (u, d) = (self.nodes.u(horizontal_idx), self.nodes.d(horizontal_idx));
+                self.nodes.set_u(d, horizontal_idx);
+                self.nodes.set_d(u, horizontal_idx);
+                self.size[self.nodes.col(horizontal_idx)] += 1;
+                self.stats.updates += 1;
             }
-            vertical_idx = self.D[vertical_idx]
+            horizontal_idx = self.nodes.l(horizontal_idx);
         }
-        self.recover(min);
+    }
 
-        false
+    // The row of node gives its color to the col of node:
+    // rows with the same color are marked with PURIFIED and the rows with other colors are hidden.
+    // Node itself keeps its color, so that unpurify knows which color to give back.
+    fn purify(&mut self, node: usize) {
+        let col = self.nodes.col(node);
+        let color = self.nodes.color(node);
+        let mut vertical_idx = self.nodes.d(col);
+        while vertical_idx != col {
+            if vertical_idx != node {
+                if self.nodes.color(vertical_idx) == color {
+                    self.nodes.set_color(vertical_idx, Self::PURIFIED);
+                } else {
+                    self.hide(vertical_idx);
+                }
+            }
+            vertical_idx = self.nodes.d(vertical_idx);
+        }
     }
 
-    pub fn dance(&mut self) -> Result<Vec<usize>, String> {
-        tracing::info!("Into dancing");
-        self.res = Some(vec![0; MAX_DEEP]);
-        let res = self.dance_internal(0);
-        if !res {
-            Err("This is a useless info to make clippy happy".to_string())
-        } else {
-            self.res
-                .clone()
-                .ok_or("This is a useless info to make clippy happy".to_string())
+    fn unpurify(&mut self, node: usize) {
+        let col = self.nodes.col(node);
+        let color = self.nodes.color(node);
+        let mut vertical_idx = self.nodes.u(col);
+        while vertical_idx != col {
+            if vertical_idx != node {
+                if self.nodes.color(vertical_idx) == Self::PURIFIED {
+                    self.nodes.set_color(vertical_idx, color);
+                } else {
+                    self.unhide(vertical_idx);
+                }
+            }
+            vertical_idx = self.nodes.u(vertical_idx);
         }
     }
-}
 
-#[cfg(test)]
-mod test {
-    use core::panic;
-    use std::{
-        sync::mpsc::{self, TryRecvError},
-        thread,
-        time::{Duration, Instant},
-    };
+    // Let the chooser pick one of the uncovered primary columns, there must be one
+    fn choose_column(&mut self) -> usize {
+        let columns = HeaderIter {
+            nodes: &self.nodes,
+            size: &self.size,
+            cur: self.nodes.r(0),
+        };
+        self.chooser.choose(columns)
+    }
 
-    use proptest::prelude::*;
+    // Cover all the other columns of the row that node belongs to.
+    // A colored element purifies its col instead, and an element with color PURIFIED
+    // needs nothing as its col already has the same color.
+    fn select(&mut self, node: usize) {
+        let mut horizontal_idx = self.nodes.r(node);
+        while horizontal_idx != node {
+            match self.nodes.color(horizontal_idx) {
+                0 => self.remove(self.nodes.col(horizontal_idx)),
+                color if color != Self::PURIFIED => self.purify(horizontal_idx),
+                _ => {}
+            }
+            horizontal_idx = self.nodes.r(horizontal_idx);
+        }
+    }
 
-    use crate::{println_cod, test_utils};
+    // Reverse of select, the columns are recovered in the opposite order
+    fn unselect(&mut self, node: usize) {
+        let mut horizontal_idx = self.nodes.l(node);
+        while horizontal_idx != node {
+            match self.nodes.color(horizontal_idx) {
+                0 => self.recover(self.nodes.col(horizontal_idx)),
+                color if color != Self::PURIFIED => self.unpurify(horizontal_idx),
+                _ => {}
+            }
+            horizontal_idx = self.nodes.l(horizontal_idx);
+        }
+    }
 
-    use super::*;
+    // Drive the search to the next solution with an explicit stack instead of recursion,
+    // so the depth is only bounded by the memory of frames.
+    // If resume is true, frames hold the last solution and we backtrack from it first.
+    // Return false once the whole search tree is visited, frames are empty and
+    // the matrix is restored by then.
+    // If the limiter stops the search, frames are left as they are for unwind.
+    // The observer is told about every step, and order gives the rows to try in each column.
+    fn search_next<O: SearchObserver, RO: RowOrder>(
+        &mut self,
+        frames: &mut Vec<Frame>,
+        resume: bool,
+        limiter: &mut Limiter,
+        observer: &mut O,
+        order: &mut RO,
+    ) -> Result<bool, DlxError> {
+        let mut descend = !resume;
+        loop {
+            if descend {
+                limiter.visit()?;
+                self.stats.enter(frames.len());
+                // if empty, we find a solution
+                if self.nodes.r(0) == 0 {
+                    observer.on_solution(frames.len());
+                    return Ok(true);
+                }
+                let min = self.choose_column();
+                self.stats.branch(frames.len(), self.size[min] as u64);
+                observer.on_enter(frames.len(), min, self.size[min] as usize);
+                self.remove(min);
+                frames.push(Frame {
+                    col: min,
+                    node: min,
+                });
+            } else {
+                // Leave the row we are trying in the top frame
+                match frames.last() {
+                    Some(frame) => self.unselect(frame.node),
+                    None => return Ok(false),
+                }
+            }
+            let frame = frames.last_mut().unwrap();
+            frame.node = order.next_node(&self.nodes, frame);
+            if frame.node == frame.col {
+                // All rows in this column have been tried, go back to the upper level
+                let col = frame.col;
+                frames.pop();
+                order.leave();
+                self.recover(col);
+                self.stats.backtracks += 1;
+                observer.on_backtrack(frames.len());
+                descend = false;
+                continue;
+            }
+            let node = frame.node;
+            observer.on_choose(frames.len() - 1, self.nodes.row(node));
+            self.select(node);
+            descend = true;
+        }
+    }
 
-    #[test]
-    fn print_dl() {
-        let mut dl = DL::new(30, 30);
-        let test_vec = vec![(1, 2), (2, 5), (3, 8), (30, 30), (25, 1), (30, 1)];
-        println!("{dl}");
-        for (row, col) in test_vec {
-            dl.insert(row, col);
+    // Undo all the frames left on the stack, so the matrix is the same as before the search
+    fn unwind(&mut self, frames: &mut Vec<Frame>) {
+        while let Some(frame) = frames.pop() {
+            if frame.node != frame.col {
+                self.unselect(frame.node);
+            }
+            self.recover(frame.col);
+        }
+    }
+
+    // Return every exact cover, each one as a list of row ids (starting with 1).
+    // The matrix is fully restored afterwards, so it can be danced again.
+    pub fn all_solutions(&mut self) -> Vec<Vec<usize>> {
+        self.solutions().collect()
+    }
+
+    // Lazily enumerate the exact covers, the next one is only searched when it is asked for.
+    pub fn solutions(&mut self) -> Solutions<'_, N, C> {
+        self.solutions_with_limits(&SearchLimits::new())
+    }
+
+    // Same as solutions, but the enumeration ends early when the deadline, the node limit or
+    // the cancel token says so, see Solutions::interrupted. max_solutions is not used,
+    // take as many solutions as needed instead.
+    pub fn solutions_with_limits(&mut self, limits: &SearchLimits) -> Solutions<'_, N, C> {
+        self.stats = SearchStats::default();
+        Solutions {
+            dl: self,
+            frames: vec![],
+            limiter: Limiter::new(limits),
+            resume: false,
+            done: false,
+            interrupted: None,
         }
-        println!("{dl}");
     }
 
-    fn test_base(r: usize, c: usize, case: Vec<Vec<usize>>, cod: bool) -> bool {
-        let mut dl = DL::new(r, c);
-        for (r_in, c_vec) in case.iter().enumerate() {
-            for (c_in, item) in c_vec.iter().enumerate() {
-                if *item == 1 {
-                    dl.insert(r_in + 1, c_in + 1)
+    // Go on with the enumeration where the cursor of an earlier Solutions left it,
+    // e.g. in another run of the program: the rows of the cursor are chosen again
+    // and the next solution is the one after the last one reported.
+    // The matrix must be built the same way, with the same rows selected.
+    pub fn resume_from(
+        &mut self,
+        cursor: &Cursor,
+        limits: &SearchLimits,
+    ) -> Result<Solutions<'_, N, C>, DlxError> {
+        let mut frames = vec![];
+        for &(col, row) in &cursor.levels {
+            match self.node_in_col(col, row) {
+                Some(node) => {
+                    self.remove(col);
+                    self.select(node);
+                    frames.push(Frame { col, node });
+                }
+                None => {
+                    self.unwind(&mut frames);
+                    return Err(DlxError::InvalidInput(format!(
+                        "the cursor does not fit the matrix: row {row} can not be chosen in col {col}"
+                    )));
                 }
             }
         }
-        println_cod!(cod, "array:\n {dl}");
-        println_cod!(cod, "array size: {:?}", dl.size);
-        let res = dl.dance();
-        println_cod!(cod, "dancing res: {:?}", res);
-        if let Ok(sol) = res {
-            let sol = sol
-                .iter()
-                .enumerate()
-                .filter(|(_, &x)| x != 0)
-                .map(|(idx, _)| case[sol[idx] - 1].clone())
-                .collect();
-            println_cod!(cod, "solution: {:?}", sol);
-            if test_utils::check_dl_res(sol, cod) {
-                return true;
+        let mut solutions = self.solutions_with_limits(limits);
+        solutions.frames = frames;
+        solutions.resume = cursor.resume;
+        Ok(solutions)
+    }
+
+    // The node of row in col, if col is an uncovered primary col and row is still in it
+    fn node_in_col(&self, col: usize, row: usize) -> Option<usize> {
+        if col == 0 || col > self.c || !self.primary[col] || self.nodes.r(self.nodes.l(col)) != col
+        {
+            return None;
+        }
+        let mut vertical_idx = self.nodes.d(col);
+        while vertical_idx != col {
+            if self.nodes.row(vertical_idx) == row {
+                return Some(vertical_idx);
             }
+            vertical_idx = self.nodes.d(vertical_idx);
         }
-        false
+        None
     }
 
-    proptest! {
-        #![proptest_config(ProptestConfig::with_cases(10000))]
-        #[test]
-        fn test_dl_pass((r, s) in (5..=30usize).prop_flat_map(|r|  (Just(r), 3..=r)), c in 5..=30usize) {
-            let ret = test_utils::generate_sparse_matrix_with_solution(r, c, s);
-            // if !test_base(r, c, case, false) {
-            //     panic!("Test Failed")
-            // }
-            let case = ret.0.clone();
-            let case_clone = case.clone();
+    // Count the exact covers without building them.
+    // With Some(limit), the search stops as soon as limit solutions are found,
+    // e.g. count_solutions(Some(2)) == 1 means the solution is unique.
+    pub fn count_solutions(&mut self, limit: Option<u64>) -> u64 {
+        let limits = SearchLimits {
+            max_solutions: limit,
+            ..Default::default()
+        };
+        self.count_solutions_with_limits(&limits).0
+    }
 
-            let case_sol = ret.1;
+    pub fn dance(&mut self) -> Result<Vec<usize>, DlxError> {
+        tracing::info!("Into dancing");
+        self.solutions().next().ok_or(DlxError::NoSolution)
+    }
+
+    // Same as dance, with the counters of the search
+    pub fn dance_with_stats(&mut self) -> (Result<Vec<usize>, DlxError>, SearchStats) {
+        let res = self.dance();
+        (res, self.stats.clone())
+    }
+
+    // Counters of the last search, e.g. dance, count_solutions or dance_min_cost.
+    // While enumerating with solutions, they cover the solutions returned so far.
+    pub fn stats(&self) -> &SearchStats {
+        &self.stats
+    }
 
-            // Set the timeout duration
-            let timeout = Duration::from_secs(10);
+    // Same as dance, but the search gives up with TimedOut, NodeLimitReached or Cancelled
+    // when one of the limits is hit. max_solutions is not used.
+    pub fn dance_with_limits(&mut self, limits: &SearchLimits) -> Result<Vec<usize>, DlxError> {
+        self.dance_observed(limits, &mut NoopObserver)
+    }
 
-            // Create a channel for thread communication
-            let (tx, rx) = mpsc::channel();
+    // Same as dance_with_limits, and observer is called along the search,
+    // e.g. a ProgressObserver to report how much of the search tree is done.
+    pub fn dance_observed<O: SearchObserver>(
+        &mut self,
+        limits: &SearchLimits,
+        observer: &mut O,
+    ) -> Result<Vec<usize>, DlxError> {
+        self.stats = SearchStats::default();
+        observer.on_start();
+        let mut limiter = Limiter::new(limits);
+        let mut frames = vec![];
+        let res = match self.search_next(&mut frames, false, &mut limiter, observer, &mut LinkOrder)
+        {
+            Ok(true) => Ok(self.rows_of(&frames)),
+            Ok(false) => Err(DlxError::NoSolution),
+            Err(err) => Err(err),
+        };
+        self.unwind(&mut frames);
+        res
+    }
 
-            // Spawn a new thread to run the test logic
-            let handle = thread::spawn(move || {
-                let res = test_base(r, c, case_clone, false);
-                let _ = tx.send(());
-                res
+    // Collect the exact covers until the search ends or a limit is hit,
+    // the status tells which one happened.
+    pub fn all_solutions_with_limits(
+        &mut self,
+        limits: &SearchLimits,
+    ) -> (Vec<Vec<usize>>, SearchStatus) {
+        let mut sols = vec![];
+        let status =
+            self.search_limited(limits, &mut NoopObserver, &mut LinkOrder, |dl, frames| {
+                sols.push(dl.rows_of(frames))
             });
+        (sols, status)
+    }
 
-            // Monitor the test thread execution time in the main thread
-            let start_time = Instant::now();
-            loop {
-                match rx.try_recv() {
-                    Ok(_) => {
-                        // Test thread completed
-                        break;
-                    }
-                    Err(TryRecvError::Empty) => {
-                        // Continue waiting
-                        if start_time.elapsed() > timeout {
-                            // Timeout occurred
-                            handle.thread().unpark(); // Attempt to wake up the thread to be forcefully terminated
-                            println!("mat:\n {}", utils::format_2d_string(&case));
-                            println!("sol: {:?}", &case_sol);
-                            test_utils::save_failed_case(&utils::Matrix(case.clone()), &case_sol, "failed_cases.txt");
-                            panic!("Test timed out");
-                        }
-                    }
-                    Err(TryRecvError::Disconnected) => {
-                        panic!("Test thread panicked or disconnected");
-                    }
+    // Find a random exact cover: the rows of each chosen column are tried in an order
+    // shuffled by rng, so the same seed gives the same solution.
+    // Use with_chooser(MrvRandomTies::new(..)) to break the ties between columns randomly too.
+    pub fn dance_random<R: Rng>(&mut self, rng: &mut R) -> Result<Vec<usize>, DlxError> {
+        let limits = SearchLimits {
+            max_solutions: Some(1),
+            ..Default::default()
+        };
+        let (sols, status) = self.all_solutions_random(rng, &limits);
+        match status {
+            SearchStatus::Interrupted(err) => Err(err),
+            _ => sols.into_iter().next().ok_or(DlxError::NoSolution),
+        }
+    }
+
+    // Same as all_solutions_with_limits, but the rows are tried in a random order as in
+    // dance_random, so the solutions come in a shuffled order and
+    // max_solutions picks that many random ones.
+    pub fn all_solutions_random<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        limits: &SearchLimits,
+    ) -> (Vec<Vec<usize>>, SearchStatus) {
+        let mut sols = vec![];
+        let mut order = Shuffled {
+            rng,
+            pending: vec![],
+        };
+        let status = self.search_limited(limits, &mut NoopObserver, &mut order, |dl, frames| {
+            sols.push(dl.rows_of(frames))
+        });
+        (sols, status)
+    }
+
+    pub fn count_solutions_with_limits(&mut self, limits: &SearchLimits) -> (u64, SearchStatus) {
+        self.count_solutions_observed(limits, &mut NoopObserver)
+    }
+
+    // Same as count_solutions_with_limits, and observer is called along the search
+    pub fn count_solutions_observed<O: SearchObserver>(
+        &mut self,
+        limits: &SearchLimits,
+        observer: &mut O,
+    ) -> (u64, SearchStatus) {
+        let mut count = 0;
+        let status = self.search_limited(limits, observer, &mut LinkOrder, |_, _| count += 1);
+        (count, status)
+    }
+
+    // Run the search under limits and call on_solution for each solution found.
+    // The matrix is restored whatever the status is.
+    fn search_limited<O: SearchObserver, RO: RowOrder>(
+        &mut self,
+        limits: &SearchLimits,
+        observer: &mut O,
+        order: &mut RO,
+        mut on_solution: impl FnMut(&Self, &[Frame]),
+    ) -> SearchStatus {
+        self.stats = SearchStats::default();
+        observer.on_start();
+        let mut limiter = Limiter::new(limits);
+        let mut frames = vec![];
+        let mut found = 0;
+        let status = loop {
+            if limits.max_solutions.is_some_and(|max| found >= max) {
+                break SearchStatus::SolutionLimit;
+            }
+            match self.search_next(&mut frames, found > 0, &mut limiter, observer, order) {
+                Ok(true) => {
+                    found += 1;
+                    on_solution(self, &frames);
                 }
-                thread::sleep(Duration::from_millis(10)); // Wait a short duration before checking again
+                Ok(false) => break SearchStatus::Complete,
+                Err(err) => break SearchStatus::Interrupted(err),
             }
+        };
+        self.unwind(&mut frames);
+        status
+    }
 
-            let result = handle.join().expect("Test thread panicked");
-            if !result {
-                println!("mat:\n {}", utils::format_2d_string(&case));
-                println!("sol: {:?}", &case_sol);
-                test_utils::save_failed_case(&utils::Matrix(case.clone()), &case_sol, "failed_cases.txt");
+    // The row ids of the selected rows, then the rows tried in frames
+    fn rows_of(&self, frames: &[Frame]) -> Vec<usize> {
+        self.selected
+            .iter()
+            .chain(frames)
+            .map(|f| self.nodes.row(f.node))
+            .collect()
+    }
+
+    // Force row into every solution: its cols are covered now, as if the search had chosen it,
+    // until deselect_rows. A row needs a primary col, and it conflicts with the selected
+    // rows when they share a col, which gives RowConflict and changes nothing.
+    // Sudoku givens are selected this way for example.
+    pub fn select_row(&mut self, row: usize) -> Result<(), DlxError> {
+        let node = self.forcible_node(row)?;
+        let col = self.nodes.col(node);
+        self.remove(col);
+        self.select(node);
+        self.selected.push(Frame { col, node });
+        Ok(())
+    }
+
+    // The rows forced by select_row, in the order they were selected
+    pub fn selected_rows(&self) -> Vec<usize> {
+        self.rows_of(&[])
+    }
+
+    // Give back all the rows forced by select_row
+    pub fn deselect_rows(&mut self) {
+        self.deselect_to(0);
+    }
+
+    // Deselect the last rows until len of them are left
+    fn deselect_to(&mut self, len: usize) {
+        while self.selected.len() > len {
+            let frame = self.selected.pop().unwrap();
+            self.unselect(frame.node);
+            self.recover(frame.col);
+        }
+    }
+
+    // Run f with rows selected on top of the selected ones, they are deselected afterwards
+    fn with_selected<T>(
+        &mut self,
+        rows: &[usize],
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> Result<T, DlxError> {
+        let len = self.selected.len();
+        for &row in rows {
+            if let Err(err) = self.select_row(row) {
+                self.deselect_to(len);
+                return Err(err);
+            }
+        }
+        let res = f(self);
+        self.deselect_to(len);
+        Ok(res)
+    }
+
+    // Dance with rows forced into the solution only this time, the solution includes them
+    pub fn dance_with(&mut self, rows: &[usize]) -> Result<Vec<usize>, DlxError> {
+        self.with_selected(rows, |dl| dl.dance())?
+    }
+
+    // A node of row in a primary col, if the row can still be chosen
+    fn forcible_node(&self, row: usize) -> Result<usize, DlxError> {
+        if row == 0 || row > self.r {
+            return Err(DlxError::InvalidInput(format!(
+                "row {row} is out of index, rows are 1..={}",
+                self.r
+            )));
+        }
+        if self.removed[row] {
+            return Err(DlxError::InvalidInput(format!("row {row} is removed")));
+        }
+        let mut primary_node = None;
+        let first_idx = self.first[row];
+        let mut idx = first_idx;
+        while idx != 0 {
+            let col = self.nodes.col(idx);
+            if self.primary[col] {
+                // A covered col is out of the header list, and a hidden row is out of its cols
+                let col_active = self.nodes.r(self.nodes.l(col)) == col;
+                let linked = self.nodes.u(self.nodes.d(idx)) == idx;
+                if !col_active || !linked {
+                    return Err(DlxError::RowConflict(row));
+                }
+                primary_node.get_or_insert(idx);
             }
-            prop_assert!(result);
+            idx = self.nodes.r(idx);
+            if idx == first_idx {
+                break;
+            }
+        }
+        primary_node.ok_or_else(|| {
+            DlxError::InvalidInput(format!("row {row} has no primary col to be chosen"))
+        })
+    }
+
+    // Search under limits with prefix rows selected first, the solutions start with them.
+    // Only the count is kept if count_only is true.
+    pub(crate) fn search_with_prefix(
+        &mut self,
+        prefix: &[usize],
+        limits: &SearchLimits,
+        count_only: bool,
+    ) -> Result<(Vec<Vec<usize>>, u64, SearchStatus), DlxError> {
+        self.with_selected(prefix, |dl| {
+            let mut sols = vec![];
+            let mut count = 0;
+            let status =
+                dl.search_limited(limits, &mut NoopObserver, &mut LinkOrder, |dl, found| {
+                    count += 1;
+                    if !count_only {
+                        sols.push(dl.rows_of(found));
+                    }
+                });
+            (sols, count, status)
+        })
+    }
+
+    // The matrix as a job with the selected rows and then the prefix rows forced, see job.rs
+    pub fn to_job(&self, prefix: &[usize]) -> Job {
+        // The selected rows purified their colored cols, the elements of the same color
+        // are marked with PURIFIED until then, so their colors are taken from the selected rows
+        let mut purified = vec![0; self.c + 1];
+        for frame in &self.selected {
+            let mut idx = self.nodes.r(frame.node);
+            while idx != frame.node {
+                let color = self.nodes.color(idx);
+                if color != 0 && color != Self::PURIFIED {
+                    purified[self.nodes.col(idx)] = color;
+                }
+                idx = self.nodes.r(idx);
+            }
+        }
+        // Jobs number the primary cols first, then the secondary ones,
+        // so the cols left are renumbered in this order
+        let live = |primary: bool| {
+            (1..=self.c).filter(move |&col| !self.retired[col] && self.primary[col] == primary)
+        };
+        let mut job_col = vec![0; self.c + 1];
+        for (i, col) in live(true).chain(live(false)).enumerate() {
+            job_col[col] = i + 1;
+        }
+        let primary = live(true).count();
+        let secondary = live(false).count();
+        let mut options = vec![vec![]; self.r];
+        let mut colors = vec![];
+        for (r, option) in options.iter_mut().enumerate() {
+            if self.removed[r + 1] {
+                continue;
+            }
+            let first_idx = self.first[r + 1];
+            let mut idx = first_idx;
+            while idx != 0 {
+                let col = self.nodes.col(idx);
+                option.push(job_col[col]);
+                let color = match self.nodes.color(idx) {
+                    color if color == Self::PURIFIED => purified[col],
+                    color => color,
+                };
+                if color > 0 {
+                    colors.push((r + 1, job_col[col], color));
+                }
+                idx = self.nodes.r(idx);
+                if idx == first_idx {
+                    break;
+                }
+            }
+        }
+        Job {
+            id: 0,
+            rows: self.r,
+            primary,
+            secondary,
+            options,
+            colors,
+            prefix: self
+                .rows_of(&[])
+                .into_iter()
+                .chain(prefix.iter().copied())
+                .collect(),
+            count_only: false,
+        }
+    }
+
+    // The weight of the row of node, in the u128 dance_min_cost adds up
+    fn node_weight(&self, node: usize) -> u128 {
+        self.weight[self.nodes.row(node)] as u128
+    }
+
+    // The least weight among the rows in col, None if col is empty
+    fn min_weight(&self, col: usize) -> Option<u64> {
+        let mut res = None;
+        let mut vertical_idx = self.nodes.d(col);
+        while vertical_idx != col {
+            let weight = self.weight[self.nodes.row(vertical_idx)];
+            res = Some(res.map_or(weight, |res: u64| res.min(weight)));
+            vertical_idx = self.nodes.d(vertical_idx);
+        }
+        res
+    }
+
+    // Find the exact cover with the least total weight by branch and bound,
+    // return its rows and the total weight.
+    // A column is not entered if even its cheapest row can not beat the best cover so far,
+    // and the rows that can not beat it are skipped.
+    // Costs are added up in u128, so it is an error only if the cheapest cover overflows u64.
+    pub fn dance_min_cost(&mut self) -> Result<(Vec<usize>, u64), DlxError> {
+        tracing::info!("Into dancing min cost");
+        self.stats = SearchStats::default();
+        let mut frames: Vec<Frame> = vec![];
+        let mut best: Option<(Vec<usize>, u128)> = None;
+        let mut cost: u128 = self.selected.iter().map(|f| self.node_weight(f.node)).sum();
+        // Whether a cover costing cost can beat the best one so far
+        let beats = |cost: u128, best: &Option<(Vec<usize>, u128)>| {
+            best.as_ref().is_none_or(|best| cost < best.1)
+        };
+        let mut descend = true;
+        loop {
+            if descend {
+                self.stats.enter(frames.len());
+                if self.nodes.r(0) == 0 {
+                    // It must be cheaper than best, or it would have been cut
+                    best = Some((self.rows_of(&frames), cost));
+                } else {
+                    let min = self.choose_column();
+                    // An empty col can not be covered
+                    let bound = self.min_weight(min).map(|w| cost + w as u128);
+                    if bound.is_some_and(|bound| beats(bound, &best)) {
+                        self.stats.branch(frames.len(), self.size[min] as u64);
+                        self.remove(min);
+                        frames.push(Frame {
+                            col: min,
+                            node: min,
+                        });
+                    }
+                }
+            }
+            let Some(frame) = frames.last_mut() else {
+                break;
+            };
+            if frame.node != frame.col {
+                self.unselect(frame.node);
+                cost -= self.node_weight(frame.node);
+            }
+            frame.node = self.nodes.d(frame.node);
+            while frame.node != frame.col && !beats(cost + self.node_weight(frame.node), &best) {
+                frame.node = self.nodes.d(frame.node);
+            }
+            if frame.node == frame.col {
+                let col = frame.col;
+                frames.pop();
+                self.recover(col);
+                self.stats.backtracks += 1;
+                descend = false;
+                continue;
+            }
+            let node = frame.node;
+            self.select(node);
+            cost += self.node_weight(node);
+            descend = true;
+        }
+        let (rows, cost) = best.ok_or(DlxError::NoSolution)?;
+        let cost = u64::try_from(cost).map_err(|_| {
+            DlxError::InvalidInput(format!(
+                "the cheapest cover costs {cost}, more than u64 holds"
+            ))
+        })?;
+        Ok((rows, cost))
+    }
+}
+
+// A subproblem of the parallel search, in the order the sequential search meets them
+enum Task {
+    // The matrix is covered above the split depth, these are the rows of the solution
+    Solved(Vec<usize>),
+    // The frames to replay on a clone, the search goes on from there
+    Open(Vec<Frame>),
+}
+
+impl<N: Nodes + Sync, C: ColumnChooser + Send + Sync> DL<N, C> {
+    // Count the exact covers on all the cores: the search tree is cut at depth,
+    // and the subtrees below are searched in parallel on clones of the matrix.
+    // Only the deadline and the cancel token of limits are used, they stop every thread.
+    // The stats add up the work of all the threads.
+    pub fn par_count(&mut self, depth: usize, limits: &SearchLimits) -> (u64, SearchStatus) {
+        let (counts, status) = self.par_search(
+            depth,
+            limits,
+            |_| 1,
+            |dl, limits| dl.count_solutions_with_limits(limits),
+        );
+        (counts.into_iter().sum(), status)
+    }
+
+    // Same as par_count, but return the solutions. With a deterministic chooser
+    // they come in the same order as in all_solutions, whatever the threads do.
+    pub fn par_solutions(
+        &mut self,
+        depth: usize,
+        limits: &SearchLimits,
+    ) -> (Vec<Vec<usize>>, SearchStatus) {
+        let (sols, status) = self.par_search(
+            depth,
+            limits,
+            |rows| vec![rows],
+            |dl, limits| dl.all_solutions_with_limits(limits),
+        );
+        (sols.into_iter().flatten().collect(), status)
+    }
+
+    // Split the search at depth, then run every open task on a clone with the rows of its
+    // prefix selected. The results are in the order of the tasks.
+    fn par_search<T: Send>(
+        &mut self,
+        depth: usize,
+        limits: &SearchLimits,
+        solved: impl Fn(Vec<usize>) -> T,
+        run: impl Fn(&mut Self, &SearchLimits) -> (T, SearchStatus) + Sync,
+    ) -> (Vec<T>, SearchStatus) {
+        self.stats = SearchStats::default();
+        let limits = SearchLimits {
+            max_nodes: None,
+            max_solutions: None,
+            ..limits.clone()
+        };
+        let tasks = match self.split(depth, &mut Limiter::new(&limits)) {
+            Ok(tasks) => tasks,
+            Err(err) => return (vec![], SearchStatus::Interrupted(err)),
+        };
+        let open: Vec<&[Frame]> = tasks
+            .iter()
+            .filter_map(|task| match task {
+                Task::Open(frames) => Some(frames.as_slice()),
+                Task::Solved(_) => None,
+            })
+            .collect();
+
+        let workers = thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(open.len());
+        let next = AtomicUsize::new(0);
+        let this = &*self;
+        let done: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = vec![];
+                        loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(prefix) = open.get(i) else {
+                                break;
+                            };
+                            let mut dl = this.clone();
+                            for &frame in prefix.iter() {
+                                dl.remove(frame.col);
+                                dl.select(frame.node);
+                                dl.selected.push(frame);
+                            }
+                            let (res, status) = run(&mut dl, &limits);
+                            done.push((i, res, status, dl.stats));
+                        }
+                        done
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        let mut open_res: Vec<_> = (0..open.len()).map(|_| None).collect();
+        for (i, res, status, stats) in done {
+            self.stats.merge(&stats, depth);
+            open_res[i] = Some((res, status));
+        }
+        let mut open_res = open_res.into_iter().map(|res| res.unwrap());
+        let mut status = SearchStatus::Complete;
+        let mut res = vec![];
+        for task in tasks {
+            match task {
+                Task::Solved(rows) => res.push(solved(rows)),
+                Task::Open(_) => {
+                    let (task_res, task_status) = open_res.next().unwrap();
+                    res.push(task_res);
+                    if status == SearchStatus::Complete {
+                        status = task_status;
+                    }
+                }
+            }
+        }
+        (res, status)
+    }
+
+    // Cut the search tree at depth into jobs with ids 0, 1, ...:
+    // one per node at depth and one per solution above it, in the order of the search.
+    // Their results together are the results of the whole search.
+    pub fn jobs(&mut self, depth: usize) -> Vec<Job> {
+        self.stats = SearchStats::default();
+        let tasks = self
+            .split(depth, &mut Limiter::default())
+            .expect("the search is not limited");
+        let template = self.to_job(&[]);
+        tasks
+            .into_iter()
+            .enumerate()
+            .map(|(id, task)| {
+                let prefix = match task {
+                    Task::Solved(rows) => rows,
+                    Task::Open(frames) => self.rows_of(&frames),
+                };
+                Job {
+                    id: id as u64,
+                    prefix,
+                    ..template.clone()
+                }
+            })
+            .collect()
+    }
+
+    // Walk the search tree down to depth and list what is found there in order:
+    // the solutions above depth and the frames of every node at depth.
+    // The matrix is restored afterwards.
+    fn split(&mut self, depth: usize, limiter: &mut Limiter) -> Result<Vec<Task>, DlxError> {
+        let mut tasks = vec![];
+        let mut frames: Vec<Frame> = vec![];
+        let mut descend = true;
+        let res = loop {
+            if descend {
+                if let Err(err) = limiter.visit() {
+                    break Err(err);
+                }
+                if self.nodes.r(0) == 0 {
+                    self.stats.enter(frames.len());
+                    tasks.push(Task::Solved(self.rows_of(&frames)));
+                } else if frames.len() == depth {
+                    // Entered again as the root of the subproblem
+                    tasks.push(Task::Open(frames.clone()));
+                } else {
+                    self.stats.enter(frames.len());
+                    let min = self.choose_column();
+                    self.stats.branch(frames.len(), self.size[min] as u64);
+                    self.remove(min);
+                    frames.push(Frame {
+                        col: min,
+                        node: min,
+                    });
+                }
+            }
+            let Some(frame) = frames.last_mut() else {
+                break Ok(tasks);
+            };
+            if frame.node != frame.col {
+                self.unselect(frame.node);
+            }
+            frame.node = self.nodes.d(frame.node);
+            if frame.node == frame.col {
+                let col = frame.col;
+                frames.pop();
+                self.recover(col);
+                self.stats.backtracks += 1;
+                descend = false;
+                continue;
+            }
+            let node = frame.node;
+            self.select(node);
+            descend = true;
+        };
+        self.unwind(&mut frames);
+        res
+    }
+}
+
+// The uncovered primary columns and their sizes, in the order of the header list
+struct HeaderIter<'a, N: Nodes> {
+    nodes: &'a N,
+    size: &'a [isize],
+    cur: usize,
+}
+
+impl<N: Nodes> Iterator for HeaderIter<'_, N> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.cur == 0 {
+            return None;
+        }
+        let col = self.cur;
+        self.cur = self.nodes.r(col);
+        Some((col, self.size[col] as usize))
+    }
+}
+
+// Order of the rows tried in the chosen columns
+trait RowOrder {
+    // The node of the next row to try in the column of frame, frame.col once they are all tried.
+    // frame.node == frame.col means the column is just chosen.
+    fn next_node<N: Nodes>(&mut self, nodes: &N, frame: &Frame) -> usize;
+
+    // The top frame is done
+    fn leave(&mut self) {}
+}
+
+// Follow the links from top to bottom
+struct LinkOrder;
+
+impl RowOrder for LinkOrder {
+    fn next_node<N: Nodes>(&mut self, nodes: &N, frame: &Frame) -> usize {
+        nodes.d(frame.node)
+    }
+}
+
+// A random order drawn when the column is chosen.
+// The rows of a chosen column are not touched until it is recovered,
+// so the list taken at that time stays valid.
+struct Shuffled<'a, R> {
+    rng: &'a mut R,
+    // The rows left to try in the columns of the frames, one list per frame
+    pending: Vec<Vec<usize>>,
+}
+
+impl<R: Rng> RowOrder for Shuffled<'_, R> {
+    fn next_node<N: Nodes>(&mut self, nodes: &N, frame: &Frame) -> usize {
+        if frame.node == frame.col {
+            let mut rows = vec![];
+            let mut vertical_idx = nodes.d(frame.col);
+            while vertical_idx != frame.col {
+                rows.push(vertical_idx);
+                vertical_idx = nodes.d(vertical_idx);
+            }
+            rows.shuffle(self.rng);
+            self.pending.push(rows);
+        }
+        self.pending
+            .last_mut()
+            .and_then(|rows| rows.pop())
+            .unwrap_or(frame.col)
+    }
+
+    fn leave(&mut self) {
+        self.pending.pop();
+    }
+}
+
+// One level of the search: the chosen column and the row node we are trying in it.
+// node == col means no row is being tried.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    col: usize,
+    node: usize,
+}
+
+// Iterator returned by DL::solutions.
+// The matrix is borrowed during the enumeration and restored when it is dropped.
+pub struct Solutions<'a, N: Nodes = SoaNodes<usize>, C: ColumnChooser = Mrv> {
+    dl: &'a mut DL<N, C>,
+    frames: Vec<Frame>,
+    limiter: Limiter,
+    resume: bool, // frames hold the last solution reported
+    done: bool,
+    interrupted: Option<DlxError>,
+}
+
+impl<N: Nodes, C: ColumnChooser> Solutions<'_, N, C> {
+    // Why the enumeration ended before visiting the whole tree, if it did
+    pub fn interrupted(&self) -> Option<&DlxError> {
+        self.interrupted.as_ref()
+    }
+
+    // Where the enumeration is, to go on later with DL::resume_from
+    pub fn cursor(&self) -> Cursor {
+        Cursor {
+            levels: self
+                .frames
+                .iter()
+                .map(|f| (f.col, self.dl.nodes.row(f.node)))
+                .collect(),
+            resume: self.resume,
+        }
+    }
+}
+
+impl<N: Nodes, C: ColumnChooser> Iterator for Solutions<'_, N, C> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let found = self.dl.search_next(
+            &mut self.frames,
+            self.resume,
+            &mut self.limiter,
+            &mut NoopObserver,
+            &mut LinkOrder,
+        );
+        match found {
+            Ok(true) => {
+                self.resume = true;
+                Some(self.dl.rows_of(&self.frames))
+            }
+            Ok(false) => {
+                // A cursor taken now resumes to the end at once
+                self.resume = true;
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                // The node the search was about to enter is entered again on resume
+                self.resume = false;
+                self.done = true;
+                self.interrupted = Some(err);
+                None
+            }
+        }
+    }
+}
+
+// A position in the enumeration of DL::solutions that can be saved with serde.
+// levels are the (col, row) chosen from the root down, the row also tells its position
+// among the rows of the col, as they are tried from top to bottom.
+// If resume is true, levels are the last solution reported and the search backtracks
+// from it, otherwise the search enters the node below levels.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cursor {
+    pub levels: Vec<(usize, usize)>,
+    pub resume: bool,
+}
+
+impl<N: Nodes, C: ColumnChooser> Drop for Solutions<'_, N, C> {
+    fn drop(&mut self) {
+        self.dl.unwind(&mut self.frames);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::panic;
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use proptest::prelude::*;
+
+    use crate::{observer::ProgressObserver, println_cod, test_utils};
+
+    use super::*;
+
+    #[test]
+    fn print_dl() {
+        let mut dl = DL::new(30, 30);
+        let test_vec = vec![(1, 2), (2, 5), (3, 8), (30, 30), (25, 1), (30, 1)];
+        println!("{dl}");
+        for (row, col) in test_vec {
+            dl.insert(row, col).unwrap();
+        }
+        println!("{dl}");
+    }
+
+    // Ok(false) if the solution is wrong or missing, Err if the search is stopped by limits
+    fn test_base(
+        case: Vec<Vec<usize>>,
+        cod: bool,
+        limits: &SearchLimits,
+    ) -> Result<bool, DlxError> {
+        let mut dl = DL::try_from(&Matrix(case.clone())).unwrap();
+        println_cod!(cod, "array:\n {dl}");
+        println_cod!(cod, "array size: {:?}", dl.size);
+        let res = dl.dance_with_limits(limits);
+        println_cod!(cod, "dancing res: {:?}", res);
+        match res {
+            Ok(sol) => {
+                let sol = sol.iter().map(|row| case[row - 1].clone()).collect();
+                println_cod!(cod, "solution: {:?}", sol);
+                Ok(test_utils::check_dl_res(sol, cod))
+            }
+            Err(DlxError::NoSolution) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(10000))]
+        #[test]
+        fn test_dl_pass((r, s) in (5..=30usize).prop_flat_map(|r|  (Just(r), 3..=r)), c in 5..=30usize) {
+            let ret = test_utils::generate_sparse_matrix_with_solution(r, c, s);
+            // if !test_base(r, c, case, false) {
+            //     panic!("Test Failed")
+            // }
+            let case = ret.0.clone();
+            let case_sol = ret.1;
+
+            // A search that runs longer than 10 seconds is reported as a failure
+            let limits = SearchLimits::with_timeout(Duration::from_secs(10));
+            let result = test_base(case.clone(), false, &limits);
+            if result != Ok(true) {
+                println!("mat:\n {}", utils::format_2d_string(&case));
+                println!("sol: {:?}", &case_sol);
+                test_utils::save_failed_case(&utils::Matrix(case.clone()), &case_sol, "failed_cases.txt");
+            }
+            prop_assert_eq!(result, Ok(true));
+        }
+    }
+
+    // Rows {1, 2}, {3, 4}, {1, 3}, {2, 4} and {1, 2, 3, 4}, which have 3 exact covers
+    fn small_dl() -> DL {
+        let mut dl = DL::new(5, 4);
+        let rows = [
+            vec![1, 2],
+            vec![3, 4],
+            vec![1, 3],
+            vec![2, 4],
+            vec![1, 2, 3, 4],
+        ];
+        for (r, cols) in rows.iter().enumerate() {
+            for &c in cols {
+                dl.insert(r + 1, c).unwrap();
+            }
+        }
+        dl
+    }
+
+    #[test]
+    fn test_all_solutions() {
+        let mut dl = small_dl();
+        let mut sols: Vec<_> = dl
+            .solutions()
+            .map(|mut sol| {
+                sol.sort();
+                sol
+            })
+            .collect();
+        sols.sort();
+        assert_eq!(sols, vec![vec![1, 2], vec![3, 4], vec![5]]);
+        // The matrix should be restored after the enumeration
+        assert_eq!(dl.all_solutions().len(), 3);
+        assert!(dl.dance().is_ok());
+    }
+
+    #[test]
+    fn test_count_solutions() {
+        let mut dl = small_dl();
+        assert_eq!(dl.count_solutions(None), 3);
+        assert_eq!(dl.count_solutions(Some(2)), 2);
+        assert_eq!(dl.count_solutions(Some(0)), 0);
+        assert_eq!(dl.count_solutions(Some(10)), 3);
+
+        let mut dl = DL::new(2, 2);
+        dl.insert(1, 1).unwrap();
+        dl.insert(2, 1).unwrap();
+        assert_eq!(dl.count_solutions(None), 0);
+    }
+
+    #[test]
+    fn test_insert_invalid() {
+        let mut dl = DL::new(3, 3);
+        assert!(matches!(dl.insert(0, 1), Err(DlxError::InvalidInput(_))));
+        assert!(matches!(dl.insert(1, 0), Err(DlxError::InvalidInput(_))));
+        assert!(matches!(dl.insert(4, 1), Err(DlxError::InvalidInput(_))));
+        assert!(matches!(dl.insert(1, 4), Err(DlxError::InvalidInput(_))));
+        dl.insert(1, 1).unwrap();
+        assert!(matches!(dl.insert(1, 1), Err(DlxError::InvalidInput(_))));
+        // Column 2 and 3 can not be covered
+        assert_eq!(dl.dance(), Err(DlxError::NoSolution));
+    }
+
+    // Queen (r, c) is row r * n + c + 1.
+    // Ranks and files are primary, the diagonals are secondary as they may stay empty.
+    fn n_queens(n: usize) -> DL {
+        let mut dl = DL::with_secondary(n * n, 2 * n, 2 * (2 * n - 1));
+        for r in 0..n {
+            for c in 0..n {
+                let row = r * n + c + 1;
+                dl.insert(row, r + 1).unwrap();
+                dl.insert(row, n + c + 1).unwrap();
+                dl.insert(row, 2 * n + r + c + 1).unwrap();
+                dl.insert(row, 2 * n + (2 * n - 1) + (n - 1 + r - c) + 1)
+                    .unwrap();
+            }
+        }
+        dl
+    }
+
+    #[test]
+    fn test_secondary_n_queens() {
+        let expected = [(1, 1), (2, 0), (3, 0), (4, 2), (5, 10), (6, 4), (8, 92)];
+        for (n, count) in expected {
+            let mut dl = n_queens(n);
+            assert_eq!(dl.count_solutions(None), count, "{n} queens");
+        }
+
+        let mut dl = n_queens(8);
+        let sol = dl.dance().unwrap();
+        assert_eq!(sol.len(), 8);
+        let queens: Vec<_> = sol
+            .iter()
+            .map(|row| ((row - 1) / 8, (row - 1) % 8))
+            .collect();
+        for (i, a) in queens.iter().enumerate() {
+            for b in queens.iter().skip(i + 1) {
+                assert_ne!(a.0, b.0);
+                assert_ne!(a.1, b.1);
+                assert_ne!(a.0 + b.1, b.0 + a.1);
+                assert_ne!(a.0 + a.1, b.0 + b.1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_secondary_only() {
+        // Without primary cols, the empty set is the only solution
+        let mut dl = DL::with_secondary(2, 0, 2);
+        dl.insert(1, 1).unwrap();
+        dl.insert(2, 2).unwrap();
+        assert_eq!(dl.all_solutions(), vec![Vec::<usize>::new()]);
+    }
+
+    #[test]
+    fn test_colored() {
+        // Example from TAOCP 7.2.2.1: primary p, q, r and secondary x, y, colors A = 1, B = 2
+        // 'p q x y:A', 'p r x:A y', 'p x:B', 'q x:A', 'r y:B'
+        let mut dl = DL::with_secondary(5, 3, 2);
+        let rows = [
+            vec![(1, 0), (2, 0), (4, 0), (5, 1)],
+            vec![(1, 0), (3, 0), (4, 1), (5, 0)],
+            vec![(1, 0), (4, 2)],
+            vec![(2, 0), (4, 1)],
+            vec![(3, 0), (5, 2)],
+        ];
+        for (r, cols) in rows.iter().enumerate() {
+            for &(c, color) in cols {
+                dl.insert_colored(r + 1, c, color).unwrap();
+            }
+        }
+        let mut sol = dl.dance().unwrap();
+        sol.sort();
+        assert_eq!(sol, vec![2, 4]);
+        assert_eq!(dl.count_solutions(None), 1);
+        assert!((0..=dl.idx).all(|idx| dl.nodes.color(idx) != usize::MAX));
+
+        // Colors are only allowed in secondary cols
+        assert!(matches!(
+            dl.insert_colored(1, 3, 1),
+            Err(DlxError::InvalidInput(_))
+        ));
+
+        // The largest color below the purified mark still conflicts with the others
+        let mut dl = DL::with_secondary(2, 1, 1);
+        assert!(matches!(
+            dl.insert_colored(1, 2, usize::MAX),
+            Err(DlxError::InvalidInput(_))
+        ));
+        for (row, color) in [(1, usize::MAX - 1), (2, 5)] {
+            dl.insert(row, 1).unwrap();
+            dl.insert_colored(row, 2, color).unwrap();
+        }
+        assert_eq!(dl.all_solutions(), vec![vec![2], vec![1]]);
+
+        // Colors and rows must fit in the index type of the nodes
+        use crate::nodes::AosNodes;
+        let max = u16::MAX as usize;
+        let mut dl = DL::<AosNodes<u16>>::with_nodes(max + 1, 1, 1);
+        assert!(dl.insert_colored(1, 2, max).is_err());
+        assert!(dl.insert(max + 1, 1).is_err());
+        dl.insert(max, 1).unwrap();
+        dl.insert_colored(max, 2, max - 1).unwrap();
+        assert_eq!(dl.all_solutions(), vec![vec![max]]);
+    }
+
+    // Check a set of rows by brute force, the case is in the same form as in insert_colored:
+    // a row is a list of (col, color) with col starting with 1.
+    fn check_colored(
+        case: &[Vec<(usize, usize)>],
+        primary: usize,
+        c: usize,
+        sol: &[usize],
+    ) -> bool {
+        let mut uncolored = vec![0; c + 1];
+        let mut colors = vec![vec![]; c + 1];
+        for &r in sol {
+            for &(col, color) in &case[r - 1] {
+                if color == 0 {
+                    uncolored[col] += 1;
+                } else if !colors[col].contains(&color) {
+                    colors[col].push(color);
+                }
+            }
+        }
+        (1..=c).all(|col| {
+            let used = uncolored[col] + colors[col].len();
+            if col <= primary {
+                used == 1
+            } else {
+                used <= 1
+            }
+        })
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(200))]
+        #[test]
+        fn test_colored_brute_force(
+            (primary, secondary, cells) in (1..=4usize, 1..=3usize, 1..=10usize).prop_flat_map(
+                |(primary, secondary, r)| {
+                    let cell = prop::option::weighted(0.4, 0..=2usize);
+                    let row = prop::collection::vec(cell, primary + secondary);
+                    (Just(primary), Just(secondary), prop::collection::vec(row, r))
+                }
+            )
+        ) {
+            let (r, c) = (cells.len(), primary + secondary);
+            // Primary cols are never colored
+            let case: Vec<Vec<(usize, usize)>> = cells
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .enumerate()
+                        .filter_map(|(idx, cell)| {
+                            cell.map(|color| (idx + 1, if idx < primary { 0 } else { color }))
+                        })
+                        .collect()
+                })
+                .collect();
+            let mut dl = DL::with_secondary(r, primary, secondary);
+            for (r_in, cols) in case.iter().enumerate() {
+                for &(col, color) in cols {
+                    dl.insert_colored(r_in + 1, col, color).unwrap();
+                }
+            }
+            let mut sols: Vec<_> = dl
+                .all_solutions()
+                .into_iter()
+                .map(|mut sol| {
+                    sol.sort();
+                    sol
+                })
+                .collect();
+            sols.sort();
+            // Rows without any primary col can not be chosen
+            let rows: Vec<_> = (1..=r)
+                .filter(|&row| case[row - 1].iter().any(|&(col, _)| col <= primary))
+                .collect();
+            let mut expected = vec![];
+            for mask in 0u64..(1 << rows.len()) {
+                let sol: Vec<_> = rows
+                    .iter()
+                    .enumerate()
+                    .filter(|(idx, _)| mask & (1 << idx) != 0)
+                    .map(|(_, &row)| row)
+                    .collect();
+                if check_colored(&case, primary, c, &sol) {
+                    expected.push(sol);
+                }
+            }
+            expected.sort();
+            prop_assert_eq!(sols, expected);
+        }
+    }
+
+    #[test]
+    fn test_min_cost() {
+        let mut dl = small_dl();
+        // {1, 2} costs 5, {3, 4} costs 4 and {5} costs 6
+        for (row, weight) in [(1, 2), (2, 3), (3, 1), (4, 3), (5, 6)] {
+            dl.set_weight(row, weight).unwrap();
+        }
+        let (mut sol, cost) = dl.dance_min_cost().unwrap();
+        sol.sort();
+        assert_eq!((sol, cost), (vec![3, 4], 4));
+        dl.set_weight(5, 3).unwrap();
+        assert_eq!(dl.dance_min_cost().unwrap(), (vec![5], 3));
+        assert_eq!(dl.count_solutions(None), 3);
+        assert!(matches!(
+            dl.set_weight(6, 1),
+            Err(DlxError::InvalidInput(_))
+        ));
+
+        let mut dl = DL::new(2, 2);
+        dl.insert_weighted(1, 1, 1).unwrap();
+        dl.insert_weighted(2, 1, 1).unwrap();
+        assert_eq!(dl.dance_min_cost(), Err(DlxError::NoSolution));
+
+        // The largest weight is a cost like any other
+        let mut dl = DL::new(3, 2);
+        dl.insert_weighted(1, 1, u64::MAX).unwrap();
+        dl.insert(1, 2).unwrap();
+        assert_eq!(dl.dance_min_cost(), Ok((vec![1], u64::MAX)));
+        dl.insert_weighted(2, 1, u64::MAX).unwrap();
+        dl.insert_weighted(3, 2, 1).unwrap();
+        assert_eq!(dl.dance_min_cost(), Ok((vec![1], u64::MAX)));
+        dl.set_weight(1, u64::MAX - 1).unwrap();
+        dl.set_weight(2, u64::MAX - 1).unwrap();
+        assert_eq!(dl.dance_min_cost(), Ok((vec![1], u64::MAX - 1)));
+        // Only a cover that overflows u64 is left
+        dl.set_weight(1, u64::MAX).unwrap();
+        dl.set_weight(3, 3).unwrap();
+        dl.remove_row(1).unwrap();
+        assert!(matches!(
+            dl.dance_min_cost(),
+            Err(DlxError::InvalidInput(_))
+        ));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(200))]
+        #[test]
+        fn test_min_cost_brute_force(
+            (case, weights) in test_utils::matrix_strategy(1..=10, 1..=8).prop_flat_map(|case| {
+                let r = case.len();
+                (Just(case), prop::collection::vec(0..10u64, r))
+            })
+        ) {
+            let mut dl = DL::try_from(&Matrix(case.clone())).unwrap();
+            for (r_in, &weight) in weights.iter().enumerate() {
+                dl.set_weight(r_in + 1, weight).unwrap();
+            }
+            let cost_of = |sol: &[usize]| sol.iter().map(|row| weights[row - 1]).sum::<u64>();
+            let expected = test_utils::brute_force_exact_covers(&case)
+                .iter()
+                .map(|sol| cost_of(sol))
+                .min();
+            match dl.dance_min_cost() {
+                Ok((mut sol, cost)) => {
+                    prop_assert_eq!(Some(cost), expected);
+                    prop_assert_eq!(cost_of(&sol), cost);
+                    sol.sort();
+                    prop_assert!(test_utils::brute_force_exact_covers(&case).contains(&sol));
+                }
+                Err(err) => {
+                    prop_assert_eq!(err, DlxError::NoSolution);
+                    prop_assert_eq!(expected, None);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sparse_nodes() {
+        // Only the ones take nodes, not row_size * col_size
+        let (r, c) = (50_000, 2_000);
+        let mut dl = DL::with_capacity(r, c, 3 * r);
+        let capacity = dl.nodes.capacity();
+        for i in 1..=r {
+            for k in 0..3 {
+                dl.insert(i, (i * 3 + k) % c + 1).unwrap();
+            }
+        }
+        assert_eq!(dl.nodes.len(), c + 1 + 3 * r);
+        assert_eq!(dl.nodes.capacity(), capacity);
+
+        // Nodes grow when there are more elements than reserved
+        let mut dl = DL::with_capacity(3, 3, 1);
+        for i in 1..=3 {
+            dl.insert(i, i).unwrap();
+        }
+        assert_eq!(dl.dance().unwrap().len(), 3);
+    }
+
+    fn sorted_solutions<N: Nodes>(case: &[Vec<usize>], c: usize) -> Vec<Vec<usize>> {
+        let (_, rows) = utils::dense_rows(case).unwrap();
+        let mut dl = DL::<N>::with_rows(c, &rows).unwrap();
+        let mut sols: Vec<_> = dl
+            .all_solutions()
+            .into_iter()
+            .map(|mut sol| {
+                sol.sort();
+                sol
+            })
+            .collect();
+        sols.sort();
+        sols
+    }
+
+    #[test]
+    fn test_node_layouts() {
+        use crate::nodes::AosNodes;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..200 {
+            let (r, c) = (rng.gen_range(1..=10), rng.gen_range(1..=8));
+            let case = test_utils::random_matrix(&mut rng, r, c);
+            let expected = test_utils::brute_force_exact_covers(&case);
+            assert_eq!(sorted_solutions::<SoaNodes<u32>>(&case, c), expected);
+            assert_eq!(sorted_solutions::<SoaNodes<u16>>(&case, c), expected);
+            assert_eq!(sorted_solutions::<AosNodes<usize>>(&case, c), expected);
+            assert_eq!(sorted_solutions::<AosNodes<u32>>(&case, c), expected);
+            assert_eq!(sorted_solutions::<AosNodes<u16>>(&case, c), expected);
+        }
+
+        // u16 indices hold 65536 nodes, the headers of 1000 cols included
+        let mut dl = DL::<AosNodes<u16>>::with_nodes(65_000, 1000, 0);
+        for i in 1..=64_535 {
+            dl.insert(i, i % 1000 + 1).unwrap();
+        }
+        assert!(matches!(
+            dl.insert(64_536, 1),
+            Err(DlxError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut dl = small_dl();
+        let before = dl.to_string();
+
+        let (sols, status) = dl.all_solutions_with_limits(&SearchLimits::new());
+        assert_eq!((sols.len(), status), (3, SearchStatus::Complete));
+
+        let limits = SearchLimits {
+            max_solutions: Some(2),
+            ..Default::default()
+        };
+        let (sols, status) = dl.all_solutions_with_limits(&limits);
+        assert_eq!((sols.len(), status), (2, SearchStatus::SolutionLimit));
+
+        let limits = SearchLimits {
+            max_nodes: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            dl.dance_with_limits(&limits),
+            Err(DlxError::NodeLimitReached)
+        );
+        assert_eq!(
+            dl.count_solutions_with_limits(&limits),
+            (0, SearchStatus::Interrupted(DlxError::NodeLimitReached))
+        );
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let limits = SearchLimits {
+            cancel: Some(cancel.clone()),
+            ..Default::default()
+        };
+        assert!(dl.dance_with_limits(&limits).is_ok());
+        cancel.store(true, Ordering::Relaxed);
+        assert_eq!(dl.dance_with_limits(&limits), Err(DlxError::Cancelled));
+
+        // The matrix is restored after every interrupted search
+        assert_eq!(dl.to_string(), before);
+        assert_eq!(dl.count_solutions(None), 3);
+    }
+
+    #[test]
+    fn test_timeout() {
+        // 16 queens have millions of solutions, the search can not finish in time
+        let mut dl = n_queens(16);
+        let limits = SearchLimits::with_timeout(Duration::from_millis(50));
+        let (_, status) = dl.count_solutions_with_limits(&limits);
+        assert_eq!(status, SearchStatus::Interrupted(DlxError::TimedOut));
+
+        // Cancel from another thread
+        let cancel = Arc::new(AtomicBool::new(false));
+        let limits = SearchLimits {
+            cancel: Some(cancel.clone()),
+            ..Default::default()
+        };
+        let handle = std::thread::spawn(move || dl.count_solutions_with_limits(&limits).1);
+        std::thread::sleep(Duration::from_millis(50));
+        cancel.store(true, Ordering::Relaxed);
+        assert_eq!(
+            handle.join().unwrap(),
+            SearchStatus::Interrupted(DlxError::Cancelled)
+        );
+    }
+
+    #[test]
+    fn test_stats() {
+        // Every row covers exactly one column, one row is chosen per level
+        let n = 5;
+        let mut dl = DL::new(n, n);
+        for i in 1..=n {
+            dl.insert(i, i).unwrap();
+        }
+        let (res, stats) = dl.dance_with_stats();
+        assert_eq!(res.unwrap().len(), n);
+        assert_eq!(stats.nodes, n as u64 + 1);
+        assert_eq!(stats.max_depth, n);
+        assert_eq!(stats.profile, vec![1; n + 1]);
+        assert_eq!(stats.branching, vec![1; n]);
+        assert_eq!(stats.backtracks, 0);
+
+        // Every node but the solutions chooses a column and comes back from it
+        let mut dl = small_dl();
+        assert_eq!(dl.count_solutions(None), 3);
+        let stats = dl.stats().clone();
+        assert_eq!(stats.nodes, stats.profile.iter().sum::<u64>());
+        assert_eq!(stats.max_depth + 1, stats.profile.len());
+        assert_eq!(stats.backtracks, stats.nodes - 3);
+        assert_eq!(stats.branching[0], 3);
+        // Everything unlinked is linked back
+        assert!(stats.updates > 0 && stats.updates.is_multiple_of(2));
+
+        // Stats are reset by the next search
+        dl.dance().unwrap();
+        assert!(dl.stats().nodes < stats.nodes);
+    }
+
+    // Write down the events to check the order they come in
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl SearchObserver for Recorder {
+        fn on_enter(&mut self, depth: usize, column: usize, candidates: usize) {
+            self.events
+                .push(format!("enter {depth} {column} {candidates}"));
+        }
+
+        fn on_choose(&mut self, depth: usize, row: usize) {
+            self.events.push(format!("choose {depth} {row}"));
+        }
+
+        fn on_backtrack(&mut self, depth: usize) {
+            self.events.push(format!("backtrack {depth}"));
+        }
+
+        fn on_solution(&mut self, depth: usize) {
+            self.events.push(format!("solution {depth}"));
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        // Rows {1} and {2}, then row 3 {1, 2} is another way to cover both
+        let mut dl = DL::new(3, 2);
+        dl.insert(1, 1).unwrap();
+        dl.insert(2, 2).unwrap();
+        dl.insert(3, 1).unwrap();
+        dl.insert(3, 2).unwrap();
+        let mut recorder = Recorder::default();
+        let (count, status) = dl.count_solutions_observed(&SearchLimits::new(), &mut recorder);
+        assert_eq!((count, status), (2, SearchStatus::Complete));
+        assert_eq!(
+            recorder.events,
+            [
+                "enter 0 1 2",
+                "choose 0 3",
+                "solution 1",
+                "choose 0 1",
+                "enter 1 2 1",
+                "choose 1 2",
+                "solution 2",
+                "backtrack 1",
+                "backtrack 0",
+            ]
+        );
+
+        let mut recorder = Recorder::default();
+        assert_eq!(
+            dl.dance_observed(&SearchLimits::new(), &mut recorder),
+            Ok(vec![3])
+        );
+        assert_eq!(recorder.events, ["enter 0 1 2", "choose 0 3", "solution 1"]);
+    }
+
+    // Sample the progress at every solution
+    #[derive(Default)]
+    struct ProgressSampler {
+        progress: ProgressObserver,
+        samples: Vec<f64>,
+    }
+
+    impl SearchObserver for ProgressSampler {
+        fn on_start(&mut self) {
+            self.progress.on_start();
+        }
+
+        fn on_enter(&mut self, depth: usize, column: usize, candidates: usize) {
+            self.progress.on_enter(depth, column, candidates);
+        }
+
+        fn on_choose(&mut self, depth: usize, row: usize) {
+            self.progress.on_choose(depth, row);
+        }
+
+        fn on_backtrack(&mut self, depth: usize) {
+            self.progress.on_backtrack(depth);
+        }
+
+        fn on_solution(&mut self, _depth: usize) {
+            self.samples.push(self.progress.progress());
+        }
+    }
+
+    #[test]
+    fn test_progress_observer() {
+        let mut dl = n_queens(8);
+        let mut sampler = ProgressSampler::default();
+        let (count, _) = dl.count_solutions_observed(&SearchLimits::new(), &mut sampler);
+        assert_eq!(count, 92);
+        assert_eq!(sampler.samples.len(), 92);
+        assert!(sampler.samples.windows(2).all(|w| w[0] <= w[1]));
+        assert!(sampler.samples.iter().all(|&p| (0.0..1.0).contains(&p)));
+        assert_eq!(sampler.progress.progress(), 1.0);
+
+        // Reusing the observer starts over from 0
+        let first = std::mem::take(&mut sampler.samples);
+        dl.count_solutions_observed(&SearchLimits::new(), &mut sampler);
+        assert_eq!(sampler.samples, first);
+        assert_eq!(sampler.progress.progress(), 1.0);
+    }
+
+    fn sorted<C: ColumnChooser>(dl: &mut DL<SoaNodes<usize>, C>) -> Vec<Vec<usize>> {
+        let mut sols: Vec<_> = dl
+            .all_solutions()
+            .into_iter()
+            .map(|mut sol| {
+                sol.sort();
+                sol
+            })
+            .collect();
+        sols.sort();
+        sols
+    }
+
+    #[test]
+    fn test_column_choosers() {
+        use crate::chooser::{FirstColumn, MrvRandomTies, WeightedMrv};
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..200 {
+            let (r, c) = (rng.gen_range(1..=10), rng.gen_range(1..=8));
+            let case = test_utils::random_matrix(&mut rng, r, c);
+            let mut dl = DL::try_from(&Matrix(case.clone())).unwrap();
+            let expected = test_utils::brute_force_exact_covers(&case);
+            let weights: Vec<f64> = (0..c).map(|_| rng.gen_range(0.0..2.0)).collect();
+            assert_eq!(sorted(&mut dl.clone().with_chooser(FirstColumn)), expected);
+            assert_eq!(
+                sorted(&mut dl.clone().with_chooser(MrvRandomTies::seeded(1))),
+                expected
+            );
+            assert_eq!(
+                sorted(&mut dl.clone().with_chooser(WeightedMrv::new(&weights))),
+                expected
+            );
+            assert_eq!(sorted(&mut dl), expected);
+        }
+
+        // MRV needs a smaller tree than taking the columns in order
+        let mut mrv = n_queens(6);
+        assert_eq!(mrv.count_solutions(None), 4);
+        let mut first = n_queens(6).with_chooser(FirstColumn);
+        assert_eq!(first.count_solutions(None), 4);
+        assert!(mrv.stats().nodes < first.stats().nodes);
+    }
+
+    #[test]
+    fn test_random_solutions() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        use crate::chooser::MrvRandomTies;
+
+        let mut dl = n_queens(8);
+        let before = dl.to_string();
+        let all = {
+            let mut all = dl.all_solutions();
+            all.iter_mut().for_each(|sol| sol.sort());
+            all.sort();
+            all
+        };
+
+        // The same seed gives the same solution, and different seeds spread over the solutions
+        let mut firsts = vec![];
+        for seed in 0..20 {
+            let sol = dl.dance_random(&mut StdRng::seed_from_u64(seed)).unwrap();
+            assert_eq!(
+                sol,
+                dl.dance_random(&mut StdRng::seed_from_u64(seed)).unwrap()
+            );
+            let mut sol = sol;
+            sol.sort();
+            assert!(all.binary_search(&sol).is_ok());
+            firsts.push(sol);
+        }
+        firsts.sort();
+        firsts.dedup();
+        assert!(firsts.len() > 1);
+
+        // Every solution still comes exactly once, in a shuffled order
+        let (mut sols, status) =
+            dl.all_solutions_random(&mut StdRng::seed_from_u64(1), &SearchLimits::new());
+        assert_eq!(status, SearchStatus::Complete);
+        assert_ne!(sols, dl.all_solutions());
+        sols.iter_mut().for_each(|sol| sol.sort());
+        sols.sort();
+        assert_eq!(sols, all);
+        assert_eq!(dl.to_string(), before);
+
+        // Random ties of columns on top of the random rows
+        let mut dl = dl.with_chooser(MrvRandomTies::seeded(3));
+        let (sols, status) = dl.all_solutions_random(
+            &mut StdRng::seed_from_u64(2),
+            &SearchLimits {
+                max_solutions: Some(5),
+                ..Default::default()
+            },
+        );
+        assert_eq!((sols.len(), status), (5, SearchStatus::SolutionLimit));
+        assert_eq!(dl.count_solutions(None), 92);
+
+        let mut dl = DL::new(2, 2);
+        dl.insert(1, 1).unwrap();
+        assert_eq!(
+            dl.dance_random(&mut StdRng::seed_from_u64(0)),
+            Err(DlxError::NoSolution)
+        );
+    }
+
+    #[test]
+    fn test_parallel() {
+        let mut dl = n_queens(8);
+        let before = dl.to_string();
+        let all = dl.all_solutions();
+        let stats = dl.stats().clone();
+        for depth in 0..=5 {
+            let (sols, status) = dl.par_solutions(depth, &SearchLimits::new());
+            assert_eq!(status, SearchStatus::Complete);
+            assert_eq!(sols, all);
+            // The threads do the same work as one thread, only split
+            assert_eq!(dl.stats(), &stats);
+            assert_eq!(
+                dl.par_count(depth, &SearchLimits::new()),
+                (92, SearchStatus::Complete)
+            );
+        }
+        assert_eq!(dl.to_string(), before);
+
+        // Solutions above the split depth are kept too
+        let mut dl = small_dl();
+        assert_eq!(
+            dl.par_solutions(10, &SearchLimits::new()).0,
+            dl.all_solutions()
+        );
+    }
+
+    #[test]
+    fn test_parallel_limits() {
+        let cancel = Arc::new(AtomicBool::new(true));
+        let limits = SearchLimits {
+            cancel: Some(cancel.clone()),
+            ..Default::default()
+        };
+        let mut dl = n_queens(8);
+        assert_eq!(
+            dl.par_count(2, &limits).1,
+            SearchStatus::Interrupted(DlxError::Cancelled)
+        );
+        cancel.store(false, Ordering::Relaxed);
+        assert_eq!(dl.par_count(2, &limits), (92, SearchStatus::Complete));
+
+        let mut dl = n_queens(16);
+        let limits = SearchLimits::with_timeout(Duration::from_millis(50));
+        assert_eq!(
+            dl.par_count(3, &limits).1,
+            SearchStatus::Interrupted(DlxError::TimedOut)
+        );
+    }
+
+    #[test]
+    fn test_select_rows() {
+        // Row 1 puts a queen in the corner, 4 of the 92 solutions do
+        let mut dl = n_queens(8);
+        let before = dl.to_string();
+        let with_corner: Vec<_> = dl
+            .all_solutions()
+            .into_iter()
+            .filter(|sol| sol.contains(&1))
+            .collect();
+        assert_eq!(with_corner.len(), 4);
+
+        dl.select_row(1).unwrap();
+        assert_eq!(dl.selected_rows(), vec![1]);
+        let sols = dl.all_solutions();
+        assert_eq!(sols.len(), 4);
+        assert!(sols.iter().all(|sol| sol[0] == 1 && sol.len() == 8));
+        assert_eq!(dl.count_solutions(None), 4);
+        assert_eq!(dl.par_solutions(2, &SearchLimits::new()).0, sols);
+        assert!(dl.jobs(1).iter().all(|job| job.prefix[0] == 1));
+
+        // Row 2 is on the same rank, and row 1 can not be taken twice
+        assert_eq!(dl.select_row(2), Err(DlxError::RowConflict(2)));
+        assert_eq!(dl.select_row(1), Err(DlxError::RowConflict(1)));
+        assert_eq!(dl.dance_with(&[13, 2]), Err(DlxError::RowConflict(2)));
+        assert_eq!(dl.selected_rows(), vec![1]);
+        let sol = dl.dance_with(&[13]).unwrap();
+        assert_eq!(sol[..2], [1, 13]);
+        assert_eq!(dl.selected_rows(), vec![1]);
+
+        dl.deselect_rows();
+        assert!(dl.selected_rows().is_empty());
+        assert_eq!(dl.to_string(), before);
+        assert_eq!(dl.count_solutions(None), 92);
+
+        // Rows only in secondary cols are never chosen by the search
+        let mut dl = DL::with_secondary(2, 1, 1);
+        dl.insert(1, 1).unwrap();
+        dl.insert(2, 2).unwrap();
+        assert!(matches!(dl.select_row(2), Err(DlxError::InvalidInput(_))));
+        assert!(matches!(dl.select_row(3), Err(DlxError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_insert_while_selected() {
+        let mut dl = DL::new(3, 3);
+        for i in 1..=3 {
+            dl.insert(i, i).unwrap();
+        }
+        let before = dl.to_string();
+        dl.select_row(1).unwrap();
+        assert!(matches!(dl.insert(1, 2), Err(DlxError::InvalidInput(_))));
+        assert!(matches!(dl.insert(2, 1), Err(DlxError::InvalidInput(_))));
+        assert!(dl.insert_weighted(3, 1, 5).is_err());
+        assert_eq!(dl.all_solutions(), vec![vec![1, 2, 3]]);
+        dl.deselect_rows();
+        assert_eq!(dl.to_string(), before);
+        assert_eq!(dl.size[1..], [1, 1, 1]);
+        dl.insert(1, 2).unwrap();
+        assert_eq!(dl.all_solutions(), vec![vec![1, 3]]);
+    }
+
+    #[test]
+    fn test_select_rows_min_cost() {
+        // Rows {1, 2} cost 5, {1} and {2} cost 1 each
+        let mut dl = DL::new(3, 2);
+        dl.insert_weighted(1, 1, 5).unwrap();
+        dl.insert_weighted(1, 2, 5).unwrap();
+        dl.insert_weighted(2, 1, 1).unwrap();
+        dl.insert_weighted(3, 2, 1).unwrap();
+        assert_eq!(dl.dance_min_cost().unwrap().1, 2);
+        dl.select_row(1).unwrap();
+        assert_eq!(dl.dance_min_cost(), Ok((vec![1], 5)));
+    }
+
+    #[test]
+    fn test_deep_solution() {
+        // Every row covers exactly one column, so the only solution needs all of them
+        let n = 1000;
+        let mut dl = DL::new(n, n);
+        for i in 1..=n {
+            dl.insert(i, i).unwrap();
+        }
+        let mut sol = dl.dance().unwrap();
+        sol.sort();
+        assert_eq!(sol, (1..=n).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_solutions_drop_restore() {
+        let mut dl = small_dl();
+        let before = dl.to_string();
+        let size = dl.size.clone();
+        let mut solutions = dl.solutions();
+        assert!(solutions.next().is_some());
+        drop(solutions);
+        assert_eq!(dl.to_string(), before);
+        assert_eq!(dl.size, size);
+        assert_eq!(dl.count_solutions(None), 3);
+    }
+
+    #[test]
+    fn test_constructors() {
+        let expected = small_dl().to_string();
+        let rows = vec![
+            vec![1, 2],
+            vec![3, 4],
+            vec![1, 3],
+            vec![2, 4],
+            vec![1, 2, 3, 4],
+        ];
+        let dense: Vec<Vec<bool>> = rows
+            .iter()
+            .map(|cols| (1..=4).map(|col| cols.contains(&col)).collect())
+            .collect();
+        let matrix = Matrix(
+            dense
+                .iter()
+                .map(|c_vec| c_vec.iter().map(|&item| item as usize).collect())
+                .collect(),
+        );
+        assert_eq!(
+            DL::from_rows(4, rows.clone()).unwrap().to_string(),
+            expected
+        );
+        assert_eq!(DL::from_dense(&dense).unwrap().to_string(), expected);
+        assert_eq!(DL::try_from(&matrix).unwrap().to_string(), expected);
+        let indptr = [0, 2, 4, 6, 8, 12];
+        let indices = [0, 1, 2, 3, 0, 2, 1, 3, 0, 1, 2, 3];
+        let mut dl = DL::from_sparse(4, &indptr, &indices).unwrap();
+        assert_eq!(dl.to_string(), expected);
+        assert_eq!(dl.count_solutions(None), 3);
+
+        // Anything but 0 and 1 is rejected, so are ragged rows and broken CSR arrays
+        let mut bad = matrix.clone();
+        bad.0[2][1] = 2;
+        assert!(matches!(DL::try_from(&bad), Err(DlxError::InvalidInput(_))));
+        bad.0[2].pop();
+        assert!(DL::try_from(&bad).is_err());
+        assert!(DL::from_dense(&[vec![true], vec![]]).is_err());
+        assert!(DL::from_rows(4, [[1, 5]]).is_err());
+        assert!(DL::from_rows(4, [[2, 2]]).is_err());
+        assert!(DL::from_sparse(4, &[0, 2, 1, 4], &indices[..4]).is_err());
+        assert!(DL::from_sparse(4, &[0, 2], &indices[..3]).is_err());
+        assert!(DL::from_sparse(4, &[0, 1], &[4]).is_err());
+        assert!(DL::from_sparse(4, &[], &[]).is_err());
+
+        // DlMulti is built the same way
+        use crate::dancinglink_multicover_v1::DlMulti;
+        let expected = DlMulti::from_rows(4, rows).unwrap().to_string();
+        assert_eq!(DlMulti::from_dense(&dense).unwrap().to_string(), expected);
+        assert_eq!(DlMulti::try_from(&matrix).unwrap().to_string(), expected);
+        let dl = DlMulti::from_sparse(4, &indptr, &indices).unwrap();
+        assert_eq!(dl.to_string(), expected);
+        assert!(DlMulti::try_from(&bad).is_err());
+    }
+
+    #[test]
+    fn test_edit_rows() {
+        let mut dl = small_dl();
+        let before = dl.to_string();
+        let all = dl.all_solutions();
+        assert_eq!(all.len(), 3);
+
+        dl.remove_row(5).unwrap();
+        dl.remove_row(1).unwrap();
+        assert_eq!(dl.all_solutions(), vec![vec![3, 4]]);
+        assert_ne!(dl.to_string(), before);
+        assert_eq!(dl.size[1..], [1, 1, 2, 2]);
+        assert!(dl.remove_row(1).is_err());
+        assert!(dl.restore_row(2).is_err());
+        assert!(dl.insert(1, 3).is_err());
+        assert!(matches!(dl.select_row(5), Err(DlxError::InvalidInput(_))));
+        assert!(dl.to_job(&[]).options[4].is_empty());
+
+        // Restored out of order, the cols are the same as before
+        dl.restore_row(5).unwrap();
+        dl.restore_row(1).unwrap();
+        assert_eq!(dl.to_string(), before);
+        assert_eq!(dl.all_solutions(), all);
+
+        // New rows take the next ids
+        assert_eq!(dl.add_row(&[1, 2, 3]).unwrap(), 6);
+        assert_eq!(dl.add_row(&[4]).unwrap(), 7);
+        assert!(dl.add_row(&[1, 1]).is_err());
+        assert!(dl.add_row(&[5]).is_err());
+        assert_eq!(dl.count_solutions(None), 4);
+        dl.remove_row(7).unwrap();
+        assert_eq!(dl.count_solutions(None), 3);
+
+        // Not while rows are selected
+        dl.select_row(2).unwrap();
+        assert!(dl.remove_row(1).is_err());
+        assert!(dl.add_row(&[1]).is_err());
+        dl.deselect_rows();
+        dl.restore_row(7).unwrap();
+        let mut sols = dl.all_solutions();
+        sols.iter_mut().for_each(|s| s.sort());
+        sols.sort();
+        assert_eq!(sols, vec![vec![1, 2], vec![3, 4], vec![5], vec![6, 7]]);
+    }
+
+    #[test]
+    fn test_edit_columns() {
+        let mut dl = small_dl();
+        let all = dl.all_solutions();
+
+        // The new col is primary, so it must be covered
+        assert_eq!(dl.add_column().unwrap(), 5);
+        assert_eq!(dl.count_solutions(None), 0);
+        assert_eq!(dl.add_row(&[5]).unwrap(), 6);
+        assert_eq!(dl.count_solutions(None), 3);
+        dl.retire_column(5).unwrap();
+        assert_eq!(dl.all_solutions(), all);
+        assert!(dl.insert(1, 5).is_err());
+        assert!(dl.add_row(&[5]).is_err());
+        assert!(dl.retire_column(5).is_err());
+        assert!(dl.retire_column(7).is_err());
+
+        // Rows 1 and 2 can no longer be chosen together
+        assert_eq!(dl.add_secondary_column().unwrap(), 6);
+        dl.insert_colored(1, 6, 1).unwrap();
+        dl.insert_colored(2, 6, 2).unwrap();
+        assert_eq!(dl.count_solutions(None), 2);
+
+        // Without col 1, rows 3 and 4 only need to cover 2, 3 and 4
+        dl.retire_column(1).unwrap();
+        let mut sols = dl.all_solutions();
+        sols.iter_mut().for_each(|s| s.sort());
+        sols.sort();
+        assert_eq!(sols, vec![vec![3, 4], vec![5]]);
+        let job = dl.to_job(&[]);
+        assert_eq!((job.primary, job.secondary), (3, 1));
+        assert_eq!(job.to_dl().unwrap().count_solutions(None), 2);
+
+        // The element moved for the header of a new col can be in a removed row
+        let mut dl = small_dl();
+        dl.remove_row(1).unwrap();
+        dl.add_column().unwrap();
+        dl.restore_row(1).unwrap();
+        dl.insert(1, 5).unwrap();
+        assert_eq!(dl.all_solutions(), vec![vec![1, 2]]);
+        dl.retire_column(5).unwrap();
+        assert_eq!(dl.all_solutions(), all);
+
+        // The element moved for the header of a new col can be in a retired col
+        let mut dl = DL::new(3, 2);
+        for (row, col) in [(1, 1), (1, 2), (2, 1), (3, 2)] {
+            dl.insert(row, col).unwrap();
+        }
+        dl.retire_column(1).unwrap();
+        dl.add_secondary_column().unwrap();
+        assert_eq!(dl.all_solutions(), vec![vec![3], vec![1]]);
+        assert_eq!(dl.dance(), Ok(vec![3]));
+        let job = dl.to_job(&[]);
+        assert_eq!(job.options, vec![vec![1], vec![], vec![1]]);
+        assert_eq!(job.to_dl().unwrap().all_solutions(), vec![vec![3], vec![1]]);
+
+        // Moved nodes keep their place when their rows are removed and restored
+        let mut dl = DL::new(3, 1);
+        for row in 1..=3 {
+            dl.insert(row, 1).unwrap();
+        }
+        let all = dl.all_solutions();
+        assert_eq!(all, vec![vec![3], vec![2], vec![1]]);
+        dl.add_secondary_column().unwrap();
+        dl.add_column().unwrap();
+        dl.insert(3, 3).unwrap();
+        dl.retire_column(3).unwrap();
+        for row in [2, 1, 3] {
+            dl.remove_row(row).unwrap();
+        }
+        for row in [1, 3, 2] {
+            dl.restore_row(row).unwrap();
+        }
+        assert_eq!(dl.all_solutions(), all);
+    }
+
+    #[test]
+    fn test_resume_from_cursor() {
+        let mut dl = n_queens(8);
+        let before = dl.to_string();
+        let all = dl.all_solutions();
+        assert_eq!(all.len(), 92);
+
+        // Pause after 30 solutions, save the cursor and go on from it
+        let mut solutions = dl.solutions();
+        let first: Vec<_> = solutions.by_ref().take(30).collect();
+        let json = serde_json::to_string(&solutions.cursor()).unwrap();
+        drop(solutions);
+        assert_eq!(dl.to_string(), before);
+        let cursor: Cursor = serde_json::from_str(&json).unwrap();
+        assert!(cursor.resume);
+        let rest: Vec<_> = dl
+            .resume_from(&cursor, &SearchLimits::new())
+            .unwrap()
+            .collect();
+        assert_eq!(first, all[..30]);
+        assert_eq!(rest, all[30..]);
+
+        // Interrupted by the node limit again and again, nothing is lost or repeated
+        let limits = SearchLimits {
+            max_nodes: Some(50),
+            ..Default::default()
+        };
+        let mut cursor = Cursor::default();
+        let mut found = vec![];
+        let mut pauses = 0;
+        loop {
+            let mut solutions = dl.resume_from(&cursor, &limits).unwrap();
+            found.extend(solutions.by_ref());
+            cursor = solutions.cursor();
+            match solutions.interrupted() {
+                Some(err) => assert_eq!(err, &DlxError::NodeLimitReached),
+                None => break,
+            }
+            pauses += 1;
+        }
+        assert!(pauses > 10);
+        assert_eq!(found, all);
+        assert_eq!(dl.to_string(), before);
+
+        // A finished enumeration resumes to nothing
+        assert!(cursor.levels.is_empty() && cursor.resume);
+        assert_eq!(dl.resume_from(&cursor, &limits).unwrap().count(), 0);
+
+        // Rows that do not fit are rejected and the matrix is left as it was
+        let bad = Cursor {
+            levels: vec![(0, 1)],
+            resume: false,
+        };
+        assert!(matches!(
+            dl.resume_from(&bad, &limits),
+            Err(DlxError::InvalidInput(_))
+        ));
+        let cursor = Cursor {
+            levels: vec![(1, 1), (1, 2)],
+            resume: false,
+        };
+        assert!(dl.resume_from(&cursor, &limits).is_err());
+        assert_eq!(dl.to_string(), before);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(200))]
+        #[test]
+        fn test_all_solutions_brute_force(case in test_utils::matrix_strategy(1..=10, 1..=8)) {
+            let mut dl = DL::try_from(&Matrix(case.clone())).unwrap();
+            let mut sols: Vec<_> = dl
+                .all_solutions()
+                .into_iter()
+                .map(|mut sol| {
+                    sol.sort();
+                    sol
+                })
+                .collect();
+            sols.sort();
+            let expected = test_utils::brute_force_exact_covers(&case);
+            prop_assert_eq!(dl.count_solutions(None), expected.len() as u64);
+            prop_assert_eq!(sols, expected);
         }
     }
 
@@ -370,7 +2937,8 @@ mod test {
         if let Ok(cases) = test_utils::load_failed_cases("failed_cases.txt") {
             let mut test_res = vec![true; cases.len()];
             for (idx, (mat, sol)) in cases.into_iter().enumerate() {
-                if !test_base(mat.0.len(), mat.0[0].len(), mat.0.clone(), true) {
+                let res = test_base(mat.0.clone(), true, &SearchLimits::new());
+                if res != Ok(true) {
                     println!(
                         "Official solution is {:?}",
                         test_utils::change_sol_base_idx(&sol)
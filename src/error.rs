@@ -0,0 +1,40 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+// Errors returned by the dancing link solvers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DlxError {
+    // The search finished and there is no solution
+    NoSolution,
+    // The search was cancelled by the caller
+    Cancelled,
+    // The search ran out of its time budget
+    TimedOut,
+    // The search visited as many nodes as it was allowed to
+    NodeLimitReached,
+    // The row can not be forced into the solution, it shares a col with a row forced before
+    RowConflict(usize),
+    // The solver was used in a wrong way, e.g. a row or col out of index
+    InvalidInput(String),
+    // The engine can not do what was asked, e.g. enumerate with an engine that finds one cover
+    Unsupported(String),
+}
+
+impl Display for DlxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DlxError::NoSolution => write!(f, "no solution"),
+            DlxError::Cancelled => write!(f, "search cancelled"),
+            DlxError::TimedOut => write!(f, "search timed out"),
+            DlxError::NodeLimitReached => write!(f, "search node limit reached"),
+            DlxError::RowConflict(row) => {
+                write!(f, "row {row} conflicts with the rows selected before")
+            }
+            DlxError::InvalidInput(msg) => write!(f, "invalid input: {msg}"),
+            DlxError::Unsupported(msg) => write!(f, "unsupported: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for DlxError {}
@@ -0,0 +1,252 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    dancinglink_v1::DL,
+    error::DlxError,
+    limits::{SearchLimits, SearchStatus},
+};
+
+// A subproblem to send to another process: the original matrix and the rows forced on it.
+// DL::jobs cuts a search into jobs, run_job searches one, and the jobs and results travel
+// as JSON lines, e.g. over the pipes of worker processes with write_json_lines and
+// read_json_lines, or serve_jobs on the worker side.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Job {
+    // Given back in the result, to tell which job it belongs to
+    pub id: u64,
+    // Sizes of the matrix, as in DL::with_secondary
+    pub rows: usize,
+    pub primary: usize,
+    pub secondary: usize,
+    // options[i] lists the cols of row i + 1
+    pub options: Vec<Vec<usize>>,
+    // (row, col, color) of the colored elements
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub colors: Vec<(usize, usize, usize)>,
+    // Rows every solution of the job starts with
+    pub prefix: Vec<usize>,
+    // Only count the solutions
+    #[serde(default)]
+    pub count_only: bool,
+}
+
+impl Job {
+    // Build the matrix of the job, without the prefix
+    pub fn to_dl(&self) -> Result<DL, DlxError> {
+        if self.options.len() > self.rows {
+            return Err(DlxError::InvalidInput(format!(
+                "job {} has {} options for {} rows",
+                self.id,
+                self.options.len(),
+                self.rows
+            )));
+        }
+        let colors: HashMap<_, _> = self
+            .colors
+            .iter()
+            .map(|&(row, col, color)| ((row, col), color))
+            .collect();
+        let mut dl = DL::with_secondary(self.rows, self.primary, self.secondary);
+        for (r_in, cols) in self.options.iter().enumerate() {
+            for &col in cols {
+                let color = colors.get(&(r_in + 1, col)).copied().unwrap_or(0);
+                dl.insert_colored(r_in + 1, col, color)?;
+            }
+        }
+        Ok(dl)
+    }
+}
+
+// What a job found, with the id of the job
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobResult {
+    pub id: u64,
+    pub count: u64,
+    // Empty if the job only counts
+    pub solutions: Vec<Vec<usize>>,
+    // Err if the job itself is wrong, e.g. its prefix rows conflict
+    pub status: Result<SearchStatus, DlxError>,
+}
+
+// Search the job under limits, the solutions start with the prefix of the job
+pub fn run_job(job: &Job, limits: &SearchLimits) -> JobResult {
+    let res = job
+        .to_dl()
+        .and_then(|mut dl| dl.search_with_prefix(&job.prefix, limits, job.count_only));
+    match res {
+        Ok((solutions, count, status)) => JobResult {
+            id: job.id,
+            count,
+            solutions,
+            status: Ok(status),
+        },
+        Err(err) => JobResult {
+            id: job.id,
+            count: 0,
+            solutions: vec![],
+            status: Err(err),
+        },
+    }
+}
+
+// Worker loop: read one job per line, write one result per line, until the input ends
+pub fn serve_jobs(
+    reader: impl BufRead,
+    mut writer: impl Write,
+    limits: &SearchLimits,
+) -> io::Result<()> {
+    for job in read_json_lines::<Job>(reader) {
+        let res = run_job(&job?, limits);
+        write_json_lines(&mut writer, [&res])?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+// Write each item as a line of JSON
+pub fn write_json_lines<T: Serialize>(
+    mut writer: impl Write,
+    items: impl IntoIterator<Item = T>,
+) -> io::Result<()> {
+    for item in items {
+        serde_json::to_writer(&mut writer, &item)?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+// Read the items written by write_json_lines, empty lines are skipped
+pub fn read_json_lines<T: DeserializeOwned>(
+    reader: impl BufRead,
+) -> impl Iterator<Item = io::Result<T>> {
+    reader
+        .lines()
+        .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn queens(n: usize) -> DL {
+        let mut dl = DL::with_secondary(n * n, 2 * n, 2 * (2 * n - 1));
+        for r in 0..n {
+            for c in 0..n {
+                let row = r * n + c + 1;
+                for col in [r + 1, n + c + 1, 2 * n + r + c + 1, 5 * n - 1 + r - c] {
+                    dl.insert(row, col).unwrap();
+                }
+            }
+        }
+        dl
+    }
+
+    fn sorted(mut sols: Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+        sols.iter_mut().for_each(|sol| sol.sort());
+        sols.sort();
+        sols
+    }
+
+    #[test]
+    fn test_jobs() {
+        let mut dl = queens(6);
+        let all = sorted(dl.all_solutions());
+        for depth in 0..=3 {
+            let jobs = dl.jobs(depth);
+            assert!(jobs.iter().enumerate().all(|(i, job)| job.id == i as u64));
+            let mut sols = vec![];
+            for job in &jobs {
+                let res = run_job(job, &SearchLimits::new());
+                assert_eq!(res.id, job.id);
+                assert_eq!(res.status, Ok(SearchStatus::Complete));
+                assert_eq!(res.count, res.solutions.len() as u64);
+                assert!(res.solutions.iter().all(|sol| sol.starts_with(&job.prefix)));
+                sols.extend(res.solutions);
+            }
+            assert_eq!(sorted(sols), all);
+
+            let count: u64 = jobs
+                .into_iter()
+                .map(|job| {
+                    let job = Job {
+                        count_only: true,
+                        ..job
+                    };
+                    let res = run_job(&job, &SearchLimits::new());
+                    assert!(res.solutions.is_empty());
+                    res.count
+                })
+                .sum();
+            assert_eq!(count, 4);
+        }
+
+        // The stats are the ones of the last split only
+        dl.jobs(2);
+        let stats = dl.stats().clone();
+        dl.jobs(2);
+        assert_eq!(dl.stats(), &stats);
+    }
+
+    #[test]
+    fn test_serve_jobs() {
+        let mut dl = queens(6);
+        let jobs = dl.jobs(2);
+        let mut input = vec![];
+        write_json_lines(&mut input, &jobs).unwrap();
+        let read: Vec<Job> = read_json_lines(input.as_slice())
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(read, jobs);
+
+        let mut output = vec![];
+        serve_jobs(input.as_slice(), &mut output, &SearchLimits::new()).unwrap();
+        let results: Vec<JobResult> = read_json_lines(output.as_slice())
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(results.len(), jobs.len());
+        let sols = results.into_iter().flat_map(|res| res.solutions).collect();
+        assert_eq!(sorted(sols), sorted(dl.all_solutions()));
+
+        assert!(serve_jobs("not a job\n".as_bytes(), vec![], &SearchLimits::new()).is_err());
+    }
+
+    #[test]
+    fn test_job_colors_and_conflicts() {
+        // Rows 1 and 2 agree on the color of col 3, row 3 does not
+        let mut dl = DL::with_secondary(3, 2, 1);
+        dl.insert(1, 1).unwrap();
+        dl.insert_colored(1, 3, 1).unwrap();
+        dl.insert(2, 2).unwrap();
+        dl.insert_colored(2, 3, 1).unwrap();
+        dl.insert(3, 2).unwrap();
+        dl.insert_colored(3, 3, 2).unwrap();
+        let job = dl.to_job(&[]);
+        assert_eq!(job.colors.len(), 3);
+        let json = serde_json::to_string(&job).unwrap();
+        assert_eq!(serde_json::from_str::<Job>(&json).unwrap(), job);
+        assert_eq!(
+            run_job(&job, &SearchLimits::new()).solutions,
+            vec![vec![1, 2]]
+        );
+
+        let job = dl.to_job(&[1, 3]);
+        let res = run_job(&job, &SearchLimits::new());
+        assert_eq!(res.status, Err(DlxError::RowConflict(3)));
+
+        // Row 2 is purified by the selected row 1, it keeps its color in the job
+        dl.select_row(1).unwrap();
+        let job = dl.to_job(&[]);
+        assert_eq!(job.prefix, vec![1]);
+        assert_eq!(job.colors.len(), 3);
+        assert_eq!(
+            run_job(&job, &SearchLimits::new()).solutions,
+            vec![vec![1, 2]]
+        );
+    }
+}
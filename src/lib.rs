@@ -1,8 +1,18 @@
+pub mod chooser;
+pub mod dancinglink_bounded_v1;
 pub mod dancinglink_multicover_v1;
 pub mod dancinglink_v1;
+pub mod error;
+pub mod job;
+pub mod limits;
+pub mod nodes;
+pub mod observer;
+pub mod problem;
+pub mod solver;
+pub mod stats;
 mod test_utils;
-mod utils;
-mod cpp_binding;
+pub mod utils;
+pub mod cpp_binding;
 
 pub fn add(left: u64, right: u64) -> u64 {
     left + right
@@ -0,0 +1,94 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::DlxError;
+
+// Bounds of a search, None means unbounded.
+// The search stops with DlxError::TimedOut after deadline, DlxError::NodeLimitReached after
+// visiting max_nodes search nodes, and DlxError::Cancelled once cancel is set to true,
+// which can be done from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+    pub deadline: Option<Instant>,
+    pub max_nodes: Option<u64>,
+    pub max_solutions: Option<u64>,
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+impl SearchLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Limits with a deadline timeout from now
+    pub fn with_timeout(timeout: Duration) -> Self {
+        SearchLimits {
+            deadline: Some(Instant::now() + timeout),
+            ..Default::default()
+        }
+    }
+}
+
+// How a search under SearchLimits ended
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SearchStatus {
+    // The whole search tree was visited
+    Complete,
+    // max_solutions solutions were found
+    SolutionLimit,
+    // Stopped by the deadline, the node limit or the cancel token
+    Interrupted(DlxError),
+}
+
+// The clock is only read once every CLOCK_PERIOD nodes, Instant::now is not free
+const CLOCK_PERIOD: u64 = 1024;
+
+// Checks SearchLimits while searching, one visit per search node
+#[derive(Debug, Default)]
+pub(crate) struct Limiter {
+    deadline: Option<Instant>,
+    max_nodes: Option<u64>,
+    cancel: Option<Arc<AtomicBool>>,
+    nodes: u64,
+}
+
+impl Limiter {
+    pub(crate) fn new(limits: &SearchLimits) -> Self {
+        Limiter {
+            deadline: limits.deadline,
+            max_nodes: limits.max_nodes,
+            cancel: limits.cancel.clone(),
+            nodes: 0,
+        }
+    }
+
+    // Count a search node, Err if the search has to stop before entering it
+    pub(crate) fn visit(&mut self) -> Result<(), DlxError> {
+        if self
+            .cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+        {
+            return Err(DlxError::Cancelled);
+        }
+        if self.max_nodes.is_some_and(|max| self.nodes >= max) {
+            return Err(DlxError::NodeLimitReached);
+        }
+        if self.nodes.is_multiple_of(CLOCK_PERIOD)
+            && self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(DlxError::TimedOut);
+        }
+        self.nodes += 1;
+        Ok(())
+    }
+}
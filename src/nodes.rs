@@ -0,0 +1,397 @@
+use std::fmt::Debug;
+
+// Storage of the nodes of DL: the links L/R/U/D, the col, row, color and insertion order
+// of each node. The index type and the memory layout are chosen by the type parameters, e.g.
+// SoaNodes<usize> (the default) keeps one Vec per field and
+// AosNodes<u32> keeps the fields of a node next to each other in 32 bytes.
+
+// Integer type used to store node indices
+pub trait NodeIdx: Copy + Default + Debug + Send + Sync + 'static {
+    // The largest index it can hold
+    const MAX: usize;
+
+    fn from_usize(v: usize) -> Self;
+    fn to_usize(self) -> usize;
+}
+
+macro_rules! impl_node_idx {
+    ($($t:ty),*) => {
+        $(
+            impl NodeIdx for $t {
+                const MAX: usize = <$t>::MAX as usize;
+
+                #[inline]
+                fn from_usize(v: usize) -> Self {
+                    debug_assert!(v <= <Self as NodeIdx>::MAX);
+                    v as $t
+                }
+
+                #[inline]
+                fn to_usize(self) -> usize {
+                    self as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_node_idx!(u16, u32, usize);
+
+// A growable list of nodes, indices are passed and returned as usize
+pub trait Nodes: Clone + Debug + Default + Send {
+    // The number of nodes it can hold at most
+    const MAX_LEN: usize;
+    // The largest value a field can hold
+    const MAX_VALUE: usize;
+
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    fn capacity(&self) -> usize;
+    fn reserve(&mut self, additional: usize);
+    // The bytes allocated on the heap for the nodes
+    fn heap_bytes(&self) -> usize;
+    // Append a node with all fields set to 0
+    fn push(&mut self);
+
+    fn l(&self, i: usize) -> usize;
+    fn r(&self, i: usize) -> usize;
+    fn u(&self, i: usize) -> usize;
+    fn d(&self, i: usize) -> usize;
+    fn col(&self, i: usize) -> usize;
+    fn row(&self, i: usize) -> usize;
+    fn color(&self, i: usize) -> usize;
+    fn seq(&self, i: usize) -> usize;
+
+    fn set_l(&mut self, i: usize, v: usize);
+    fn set_r(&mut self, i: usize, v: usize);
+    fn set_u(&mut self, i: usize, v: usize);
+    fn set_d(&mut self, i: usize, v: usize);
+    fn set_col(&mut self, i: usize, v: usize);
+    fn set_row(&mut self, i: usize, v: usize);
+    fn set_color(&mut self, i: usize, v: usize);
+    fn set_seq(&mut self, i: usize, v: usize);
+}
+
+// Struct of arrays: one Vec per field
+#[derive(Debug, Clone, Default)]
+pub struct SoaNodes<I = usize> {
+    l: Vec<I>,
+    r: Vec<I>,
+    u: Vec<I>,
+    d: Vec<I>,
+    col: Vec<I>,
+    row: Vec<I>,
+    color: Vec<I>,
+    seq: Vec<I>,
+}
+
+impl<I: NodeIdx> Nodes for SoaNodes<I> {
+    // Index MAX is the last one, so there are MAX + 1 nodes
+    const MAX_LEN: usize = I::MAX.saturating_add(1);
+    const MAX_VALUE: usize = I::MAX;
+
+    fn len(&self) -> usize {
+        self.l.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.l.capacity()
+    }
+
+    fn heap_bytes(&self) -> usize {
+        let fields = [
+            &self.l,
+            &self.r,
+            &self.u,
+            &self.d,
+            &self.col,
+            &self.row,
+            &self.color,
+            &self.seq,
+        ];
+        fields.iter().map(|v| v.capacity()).sum::<usize>() * std::mem::size_of::<I>()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.l.reserve(additional);
+        self.r.reserve(additional);
+        self.u.reserve(additional);
+        self.d.reserve(additional);
+        self.col.reserve(additional);
+        self.row.reserve(additional);
+        self.color.reserve(additional);
+        self.seq.reserve(additional);
+    }
+
+    fn push(&mut self) {
+        self.l.push(I::default());
+        self.r.push(I::default());
+        self.u.push(I::default());
+        self.d.push(I::default());
+        self.col.push(I::default());
+        self.row.push(I::default());
+        self.color.push(I::default());
+        self.seq.push(I::default());
+    }
+
+    #[inline]
+    fn l(&self, i: usize) -> usize {
+        self.l[i].to_usize()
+    }
+
+    #[inline]
+    fn r(&self, i: usize) -> usize {
+        self.r[i].to_usize()
+    }
+
+    #[inline]
+    fn u(&self, i: usize) -> usize {
+        self.u[i].to_usize()
+    }
+
+    #[inline]
+    fn d(&self, i: usize) -> usize {
+        self.d[i].to_usize()
+    }
+
+    #[inline]
+    fn col(&self, i: usize) -> usize {
+        self.col[i].to_usize()
+    }
+
+    #[inline]
+    fn row(&self, i: usize) -> usize {
+        self.row[i].to_usize()
+    }
+
+    #[inline]
+    fn color(&self, i: usize) -> usize {
+        self.color[i].to_usize()
+    }
+
+    #[inline]
+    fn seq(&self, i: usize) -> usize {
+        self.seq[i].to_usize()
+    }
+
+    #[inline]
+    fn set_l(&mut self, i: usize, v: usize) {
+        self.l[i] = I::from_usize(v);
+    }
+
+    #[inline]
+    fn set_r(&mut self, i: usize, v: usize) {
+        self.r[i] = I::from_usize(v);
+    }
+
+    #[inline]
+    fn set_u(&mut self, i: usize, v: usize) {
+        self.u[i] = I::from_usize(v);
+    }
+
+    #[inline]
+    fn set_d(&mut self, i: usize, v: usize) {
+        self.d[i] = I::from_usize(v);
+    }
+
+    #[inline]
+    fn set_col(&mut self, i: usize, v: usize) {
+        self.col[i] = I::from_usize(v);
+    }
+
+    #[inline]
+    fn set_row(&mut self, i: usize, v: usize) {
+        self.row[i] = I::from_usize(v);
+    }
+
+    #[inline]
+    fn set_color(&mut self, i: usize, v: usize) {
+        self.color[i] = I::from_usize(v);
+    }
+
+    #[inline]
+    fn set_seq(&mut self, i: usize, v: usize) {
+        self.seq[i] = I::from_usize(v);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+struct Node<I> {
+    l: I,
+    r: I,
+    u: I,
+    d: I,
+    col: I,
+    row: I,
+    color: I,
+    seq: I,
+}
+
+// Array of structs: the fields of a node are interleaved,
+// so following a link and unlinking the node hit the same cache line
+#[derive(Debug, Clone, Default)]
+pub struct AosNodes<I = usize> {
+    nodes: Vec<Node<I>>,
+}
+
+impl<I: NodeIdx> Nodes for AosNodes<I> {
+    const MAX_LEN: usize = I::MAX.saturating_add(1);
+    const MAX_VALUE: usize = I::MAX;
+
+    fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.nodes.capacity()
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.nodes.capacity() * std::mem::size_of::<Node<I>>()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+    }
+
+    fn push(&mut self) {
+        self.nodes.push(Node::default());
+    }
+
+    #[inline]
+    fn l(&self, i: usize) -> usize {
+        self.nodes[i].l.to_usize()
+    }
+
+    #[inline]
+    fn r(&self, i: usize) -> usize {
+        self.nodes[i].r.to_usize()
+    }
+
+    #[inline]
+    fn u(&self, i: usize) -> usize {
+        self.nodes[i].u.to_usize()
+    }
+
+    #[inline]
+    fn d(&self, i: usize) -> usize {
+        self.nodes[i].d.to_usize()
+    }
+
+    #[inline]
+    fn col(&self, i: usize) -> usize {
+        self.nodes[i].col.to_usize()
+    }
+
+    #[inline]
+    fn row(&self, i: usize) -> usize {
+        self.nodes[i].row.to_usize()
+    }
+
+    #[inline]
+    fn color(&self, i: usize) -> usize {
+        self.nodes[i].color.to_usize()
+    }
+
+    #[inline]
+    fn seq(&self, i: usize) -> usize {
+        self.nodes[i].seq.to_usize()
+    }
+
+    #[inline]
+    fn set_l(&mut self, i: usize, v: usize) {
+        self.nodes[i].l = I::from_usize(v);
+    }
+
+    #[inline]
+    fn set_r(&mut self, i: usize, v: usize) {
+        self.nodes[i].r = I::from_usize(v);
+    }
+
+    #[inline]
+    fn set_u(&mut self, i: usize, v: usize) {
+        self.nodes[i].u = I::from_usize(v);
+    }
+
+    #[inline]
+    fn set_d(&mut self, i: usize, v: usize) {
+        self.nodes[i].d = I::from_usize(v);
+    }
+
+    #[inline]
+    fn set_col(&mut self, i: usize, v: usize) {
+        self.nodes[i].col = I::from_usize(v);
+    }
+
+    #[inline]
+    fn set_row(&mut self, i: usize, v: usize) {
+        self.nodes[i].row = I::from_usize(v);
+    }
+
+    #[inline]
+    fn set_color(&mut self, i: usize, v: usize) {
+        self.nodes[i].color = I::from_usize(v);
+    }
+
+    #[inline]
+    fn set_seq(&mut self, i: usize, v: usize) {
+        self.nodes[i].seq = I::from_usize(v);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip<N: Nodes>() {
+        let mut nodes = N::default();
+        assert!(nodes.is_empty());
+        nodes.reserve(3);
+        assert!(nodes.capacity() >= 3);
+        for _ in 0..3 {
+            nodes.push();
+        }
+        assert_eq!(nodes.len(), 3);
+        nodes.set_l(1, 2);
+        nodes.set_r(1, 0);
+        nodes.set_u(2, 1);
+        nodes.set_d(2, 2);
+        nodes.set_col(0, 1);
+        nodes.set_row(1, 2);
+        nodes.set_color(2, N::MAX_VALUE);
+        nodes.set_seq(2, 1);
+        assert_eq!(
+            (nodes.l(1), nodes.r(1), nodes.u(2), nodes.d(2), nodes.col(0)),
+            (2, 0, 1, 2, 1)
+        );
+        assert_eq!(
+            (nodes.row(1), nodes.color(2), nodes.seq(2)),
+            (2, N::MAX_VALUE, 1)
+        );
+        assert_eq!((nodes.l(0), nodes.d(1), nodes.color(1)), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_nodes() {
+        round_trip::<SoaNodes<usize>>();
+        round_trip::<SoaNodes<u32>>();
+        round_trip::<SoaNodes<u16>>();
+        round_trip::<AosNodes<usize>>();
+        round_trip::<AosNodes<u32>>();
+        round_trip::<AosNodes<u16>>();
+        assert_eq!(<AosNodes<u16> as Nodes>::MAX_LEN, 1 << 16);
+        assert_eq!(<AosNodes<u16> as Nodes>::MAX_VALUE, u16::MAX as usize);
+        assert_eq!(std::mem::size_of::<Node<u32>>(), 32);
+
+        // The same nodes take half the memory with u32 indices
+        let mut wide = AosNodes::<usize>::default();
+        let mut narrow = SoaNodes::<u32>::default();
+        wide.reserve(10);
+        narrow.reserve(10);
+        assert_eq!(wide.heap_bytes(), wide.capacity() * 64);
+        assert_eq!(narrow.heap_bytes(), narrow.capacity() * 32);
+    }
+}
@@ -0,0 +1,85 @@
+// Hooks called by the search of DL, every method does nothing by default,
+// so an observer only implements the events it cares about.
+// Depth d means d rows are chosen, the root of the search is at depth 0.
+pub trait SearchObserver {
+    // A new search starts, the same observer may be reused for several searches
+    fn on_start(&mut self) {}
+
+    // A node at depth is entered and column is chosen, it has candidates rows to try
+    fn on_enter(&mut self, _depth: usize, _column: usize, _candidates: usize) {}
+
+    // The next candidate row of the column chosen at depth is tried,
+    // the rows of the current prefix are the last ones chosen at depths 0..=depth
+    fn on_choose(&mut self, _depth: usize, _row: usize) {}
+
+    // All the candidates of the column chosen at depth are tried, the search goes back up
+    fn on_backtrack(&mut self, _depth: usize) {}
+
+    // A solution of depth rows is found
+    fn on_solution(&mut self, _depth: usize) {}
+}
+
+// The observer used when nobody is watching, its calls are compiled away
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopObserver;
+
+impl SearchObserver for NoopObserver {}
+
+// Estimate the fraction of the search tree already visited from the branch positions,
+// as in Knuth's progress report: trying the k-th of n candidates at the root means about
+// (k - 1) / n of the tree is done, the same goes on for the deeper levels with
+// the product of their branchings.
+// Code Reference: TAOCP Volume 4B, 7.2.2.1
+#[derive(Debug, Clone, Default)]
+pub struct ProgressObserver {
+    // (rows tried, candidates) of the columns on the current path
+    path: Vec<(usize, usize)>,
+    finished: bool,
+}
+
+impl ProgressObserver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A number in [0, 1], 1 once the whole tree is visited
+    pub fn progress(&self) -> f64 {
+        if self.finished {
+            return 1.0;
+        }
+        let mut res = 0.0;
+        let mut scale = 1.0;
+        for &(tried, candidates) in &self.path {
+            if candidates == 0 || tried == 0 {
+                break;
+            }
+            scale /= candidates as f64;
+            res += (tried - 1) as f64 * scale;
+        }
+        res
+    }
+}
+
+impl SearchObserver for ProgressObserver {
+    fn on_start(&mut self) {
+        self.path.clear();
+        self.finished = false;
+    }
+
+    fn on_enter(&mut self, depth: usize, _column: usize, candidates: usize) {
+        self.path.truncate(depth);
+        self.path.push((0, candidates));
+    }
+
+    fn on_choose(&mut self, depth: usize, _row: usize) {
+        self.path.truncate(depth + 1);
+        self.path[depth].0 += 1;
+    }
+
+    fn on_backtrack(&mut self, depth: usize) {
+        self.path.truncate(depth);
+        if depth == 0 {
+            self.finished = true;
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::{dancinglink_multicover_v1::DlMulti, dancinglink_v1::DL, error::DlxError};
+
+// Build a problem from named items and labelled options instead of 1-based indices.
+// Items can be anything hashable (&str, enums, tuples...), options are lists of items.
+// It compiles down to DL or DlMulti, and the solutions are translated back to option labels.
+#[derive(Debug, Clone)]
+pub struct ProblemBuilder<I, O> {
+    primary: Vec<I>,
+    secondary: Vec<I>,
+    // item -> (is secondary, idx in primary or secondary)
+    items: HashMap<I, (bool, usize)>,
+    options: Vec<(O, Vec<I>)>,
+}
+
+impl<I, O> Default for ProblemBuilder<I, O> {
+    fn default() -> Self {
+        ProblemBuilder {
+            primary: vec![],
+            secondary: vec![],
+            items: HashMap::new(),
+            options: vec![],
+        }
+    }
+}
+
+impl<I: Eq + Hash + Clone + std::fmt::Debug, O> ProblemBuilder<I, O> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // An item every solution must cover exactly once
+    pub fn item(&mut self, item: I) -> Result<&mut Self, DlxError> {
+        self.declare(item, false)
+    }
+
+    // An item a solution may cover at most once
+    pub fn secondary_item(&mut self, item: I) -> Result<&mut Self, DlxError> {
+        self.declare(item, true)
+    }
+
+    fn declare(&mut self, item: I, secondary: bool) -> Result<&mut Self, DlxError> {
+        if self.items.contains_key(&item) {
+            return Err(DlxError::InvalidInput(format!(
+                "item {item:?} is declared twice"
+            )));
+        }
+        let list = if secondary {
+            &mut self.secondary
+        } else {
+            &mut self.primary
+        };
+        self.items.insert(item.clone(), (secondary, list.len()));
+        list.push(item);
+        Ok(self)
+    }
+
+    // An option covers the given items, all of them must be declared before
+    pub fn option(
+        &mut self,
+        label: O,
+        items: impl IntoIterator<Item = I>,
+    ) -> Result<&mut Self, DlxError> {
+        let items: Vec<I> = items.into_iter().collect();
+        if let Some(item) = items.iter().find(|item| !self.items.contains_key(item)) {
+            return Err(DlxError::InvalidInput(format!(
+                "item {item:?} is not declared"
+            )));
+        }
+        self.options.push((label, items));
+        Ok(self)
+    }
+
+    // Col id in DL: primary items first, then secondary ones
+    fn col_of(&self, item: &I) -> usize {
+        match self.items[item] {
+            (false, idx) => idx + 1,
+            (true, idx) => self.primary.len() + idx + 1,
+        }
+    }
+
+    // Build an exact cover problem, secondary items are secondary cols in DL
+    pub fn build(self) -> Result<Problem<O>, DlxError> {
+        let mut dl =
+            DL::with_secondary(self.options.len(), self.primary.len(), self.secondary.len());
+        for (r_in, (_, items)) in self.options.iter().enumerate() {
+            for item in items {
+                dl.insert(r_in + 1, self.col_of(item))?;
+            }
+        }
+        let labels = self.options.into_iter().map(|(label, _)| label).collect();
+        Ok(Problem { dl, labels })
+    }
+
+    // Build a multicover problem, where every item must be covered at least once
+    pub fn build_multi(self) -> Result<MultiProblem<O>, DlxError> {
+        if !self.secondary.is_empty() {
+            return Err(DlxError::InvalidInput(
+                "multicover does not support secondary items".to_string(),
+            ));
+        }
+        let rows = self
+            .options
+            .iter()
+            .map(|(_, items)| items.iter().map(|item| self.col_of(item)));
+        let dl = DlMulti::from_rows(self.primary.len(), rows)?;
+        let labels = self.options.into_iter().map(|(label, _)| label).collect();
+        Ok(MultiProblem { dl, labels })
+    }
+}
+
+// Row ids of DL start with 1
+fn to_labels<O>(labels: &[O], rows: Vec<usize>) -> Vec<&O> {
+    rows.into_iter().map(|row| &labels[row - 1]).collect()
+}
+
+// An exact cover problem built by ProblemBuilder
+#[derive(Debug, Clone)]
+pub struct Problem<O> {
+    dl: DL,
+    labels: Vec<O>,
+}
+
+impl<O> Problem<O> {
+    pub fn dl(&self) -> &DL {
+        &self.dl
+    }
+
+    pub fn dance(&mut self) -> Result<Vec<&O>, DlxError> {
+        let rows = self.dl.dance()?;
+        Ok(to_labels(&self.labels, rows))
+    }
+
+    pub fn solutions(&mut self) -> impl Iterator<Item = Vec<&O>> + '_ {
+        let labels = &self.labels;
+        self.dl.solutions().map(move |rows| to_labels(labels, rows))
+    }
+
+    pub fn count_solutions(&mut self, limit: Option<u64>) -> u64 {
+        self.dl.count_solutions(limit)
+    }
+}
+
+// A multicover problem built by ProblemBuilder
+#[derive(Debug, Clone)]
+pub struct MultiProblem<O> {
+    dl: DlMulti,
+    labels: Vec<O>,
+}
+
+impl<O> MultiProblem<O> {
+    pub fn dl(&self) -> &DlMulti {
+        &self.dl
+    }
+
+    pub fn dance(&mut self) -> Result<Vec<&O>, DlxError> {
+        let rows = self.dl.dance()?;
+        Ok(to_labels(&self.labels, rows))
+    }
+
+    pub fn min_cover(&mut self) -> Result<Vec<&O>, DlxError> {
+        let rows = self.dl.min_cover()?;
+        Ok(to_labels(&self.labels, rows))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_named_exact_cover() {
+        let mut builder = ProblemBuilder::new();
+        for item in ["a", "b", "c", "d"] {
+            builder.item(item).unwrap();
+        }
+        builder
+            .option("ab", ["a", "b"])
+            .unwrap()
+            .option("cd", ["c", "d"])
+            .unwrap()
+            .option("ac", ["a", "c"])
+            .unwrap()
+            .option("bd", ["b", "d"])
+            .unwrap()
+            .option("abcd", ["a", "b", "c", "d"])
+            .unwrap();
+        let mut problem = builder.build().unwrap();
+        let mut sols: Vec<Vec<&str>> = problem
+            .solutions()
+            .map(|sol| {
+                let mut sol: Vec<_> = sol.into_iter().copied().collect();
+                sol.sort();
+                sol
+            })
+            .collect();
+        sols.sort();
+        assert_eq!(sols, vec![vec!["ab", "cd"], vec!["abcd"], vec!["ac", "bd"]]);
+        assert_eq!(problem.count_solutions(None), 3);
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum Item {
+        Rank(usize),
+        File(usize),
+        Diag(usize),
+        AntiDiag(usize),
+    }
+
+    #[test]
+    fn test_named_queens() {
+        let n = 6;
+        let mut builder = ProblemBuilder::new();
+        for i in 0..n {
+            builder.item(Item::Rank(i)).unwrap();
+            builder.item(Item::File(i)).unwrap();
+        }
+        for i in 0..2 * n - 1 {
+            builder.secondary_item(Item::Diag(i)).unwrap();
+            builder.secondary_item(Item::AntiDiag(i)).unwrap();
+        }
+        for r in 0..n {
+            for c in 0..n {
+                let items = [
+                    Item::Rank(r),
+                    Item::File(c),
+                    Item::Diag(r + c),
+                    Item::AntiDiag(n - 1 + r - c),
+                ];
+                builder.option((r, c), items).unwrap();
+            }
+        }
+        let mut problem = builder.build().unwrap();
+        assert_eq!(problem.count_solutions(None), 4);
+        let sol = problem.dance().unwrap();
+        assert_eq!(sol.len(), n);
+    }
+
+    #[test]
+    fn test_named_min_cover() {
+        let mut builder = ProblemBuilder::new();
+        for item in 1..=4 {
+            builder.item(item).unwrap();
+        }
+        builder.option("low", [1, 2]).unwrap();
+        builder.option("high", [3, 4]).unwrap();
+        builder.option("all", [1, 2, 3, 4]).unwrap();
+        let mut problem = builder.build_multi().unwrap();
+        assert_eq!(problem.min_cover().unwrap(), vec![&"all"]);
+    }
+
+    #[test]
+    fn test_empty_builder() {
+        // Nothing to cover, so choosing no option is the only solution
+        let builder: ProblemBuilder<&str, usize> = ProblemBuilder::new();
+        let mut problem = builder.clone().build().unwrap();
+        assert_eq!(problem.dance().unwrap(), Vec::<&usize>::new());
+        assert_eq!(problem.count_solutions(None), 1);
+        let mut problem = builder.build_multi().unwrap();
+        assert_eq!(problem.dance().unwrap(), Vec::<&usize>::new());
+        assert_eq!(problem.min_cover().unwrap(), Vec::<&usize>::new());
+    }
+
+    #[test]
+    fn test_builder_invalid() {
+        let mut builder: ProblemBuilder<&str, usize> = ProblemBuilder::new();
+        builder.item("a").unwrap();
+        assert!(matches!(builder.item("a"), Err(DlxError::InvalidInput(_))));
+        assert!(matches!(
+            builder.secondary_item("a"),
+            Err(DlxError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            builder.option(1, ["b"]),
+            Err(DlxError::InvalidInput(_))
+        ));
+        builder.option(1, ["a", "a"]).unwrap();
+        assert!(matches!(builder.build(), Err(DlxError::InvalidInput(_))));
+
+        let mut builder: ProblemBuilder<&str, usize> = ProblemBuilder::new();
+        builder.item("a").unwrap();
+        builder.secondary_item("b").unwrap();
+        assert!(matches!(
+            builder.build_multi(),
+            Err(DlxError::InvalidInput(_))
+        ));
+    }
+}
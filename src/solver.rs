@@ -0,0 +1,73 @@
+use crate::{error::DlxError, stats::SearchStats};
+
+// What the cover engines share, so the same code can run on any of them:
+// DL (dancinglink_v1), DlMulti (dancinglink_multicover_v1) and the C++ DLX (cpp_binding).
+// What a cover is depends on the engine, DL finds exact covers,
+// DlMulti and the C++ DLX find covers where each col is covered at least once.
+// Rows and cols are numbered from 1 as everywhere else.
+pub trait CoverSolver: Sized {
+    // A matrix of col_size cols, rows[i] lists the cols of row i + 1
+    fn from_rows(col_size: usize, rows: &[Vec<usize>]) -> Result<Self, DlxError>;
+
+    // One cover, as row ids
+    fn solve(&mut self) -> Result<Vec<usize>, DlxError>;
+
+    // Every cover, Unsupported if the engine can not enumerate them
+    fn solve_all(&mut self) -> Result<Vec<Vec<usize>>, DlxError>;
+
+    // The number of covers, it stops at limit if there is one
+    fn count(&mut self, limit: Option<u64>) -> Result<u64, DlxError>;
+
+    // The counters of the last search, None if the engine does not keep them
+    fn search_stats(&self) -> Option<&SearchStats>;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{cpp_binding::CppDlx, dancinglink_multicover_v1::DlMulti, dancinglink_v1::DL};
+
+    fn rows() -> Vec<Vec<usize>> {
+        vec![vec![1, 2], vec![3], vec![2, 3], vec![1], vec![1, 2, 3]]
+    }
+
+    // Solve through the trait only, every col must be covered
+    fn solve_covers<S: CoverSolver>() -> Vec<usize> {
+        let rows = rows();
+        let mut solver = S::from_rows(3, &rows).unwrap();
+        let res = solver.solve().unwrap();
+        for col in 1..=3 {
+            assert!(res.iter().any(|&row| rows[row - 1].contains(&col)));
+        }
+        assert!(S::from_rows(3, &[vec![4]]).is_err());
+        res
+    }
+
+    #[test]
+    fn test_cover_solvers() {
+        solve_covers::<DL>();
+        let mut dl: DL = CoverSolver::from_rows(3, &rows()).unwrap();
+        let mut all = dl.solve_all().unwrap();
+        all.iter_mut().for_each(|s| s.sort());
+        all.sort();
+        assert_eq!(all, vec![vec![1, 2], vec![3, 4], vec![5]]);
+        assert_eq!(dl.count(Some(2)).unwrap(), 2);
+        assert!(dl.search_stats().unwrap().nodes > 0);
+
+        solve_covers::<DlMulti>();
+        let mut dl = DlMulti::from_rows(3, rows()).unwrap();
+        // Row 5 is found for each of the 3 cols, it is only reported once
+        assert_eq!(dl.dance().unwrap(), vec![5, 5, 5]);
+        assert_eq!(dl.solve().unwrap(), vec![5]);
+        assert!(matches!(dl.solve_all(), Err(DlxError::Unsupported(_))));
+        assert!(matches!(dl.count(None), Err(DlxError::Unsupported(_))));
+
+        // The least rows are needed
+        assert_eq!(solve_covers::<CppDlx>(), vec![5]);
+        let mut dlx = CppDlx::from_rows(3, &rows()).unwrap();
+        assert!(matches!(dlx.solve_all(), Err(DlxError::Unsupported(_))));
+        assert!(dlx.search_stats().is_none());
+        let mut dlx = CppDlx::from_rows(3, &[vec![1], vec![2]]).unwrap();
+        assert_eq!(dlx.solve(), Err(DlxError::NoSolution));
+    }
+}
@@ -0,0 +1,57 @@
+// Counters of how hard a search worked, the solvers keep the ones of their last search.
+// Depth d means d rows are chosen, so the root of the search is at depth 0.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    // Nodes unlinked or linked back, by covering and uncovering columns
+    pub updates: u64,
+    // Search nodes entered, the root included
+    pub nodes: u64,
+    // Times the search went back up a level after trying all the rows of a column
+    pub backtracks: u64,
+    // The deepest level entered
+    pub max_depth: usize,
+    // profile[d] is the number of nodes entered at depth d
+    pub profile: Vec<u64>,
+    // branching[d] sums the rows of the columns chosen at depth d,
+    // so branching[d] / profile[d] is about the mean branching factor there
+    pub branching: Vec<u64>,
+}
+
+impl SearchStats {
+    pub(crate) fn enter(&mut self, depth: usize) {
+        self.nodes += 1;
+        self.max_depth = self.max_depth.max(depth);
+        self.enter_many(depth, 1);
+    }
+
+    // A column with rows rows is chosen at depth
+    pub(crate) fn branch(&mut self, depth: usize, rows: u64) {
+        if self.branching.len() <= depth {
+            self.branching.resize(depth + 1, 0);
+        }
+        self.branching[depth] += rows;
+    }
+
+    // Add the counters of a search that started at depth, e.g. a subproblem of a parallel search
+    pub(crate) fn merge(&mut self, other: &SearchStats, depth: usize) {
+        self.updates += other.updates;
+        self.nodes += other.nodes;
+        self.backtracks += other.backtracks;
+        self.max_depth = self.max_depth.max(other.max_depth + depth);
+        for (d, &nodes) in other.profile.iter().enumerate() {
+            if nodes > 0 {
+                self.enter_many(d + depth, nodes);
+            }
+        }
+        for (d, &rows) in other.branching.iter().enumerate() {
+            self.branch(d + depth, rows);
+        }
+    }
+
+    fn enter_many(&mut self, depth: usize, nodes: u64) {
+        if self.profile.len() <= depth {
+            self.profile.resize(depth + 1, 0);
+        }
+        self.profile[depth] += nodes;
+    }
+}
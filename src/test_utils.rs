@@ -1,9 +1,10 @@
 #![cfg(test)]
 
 use std::{
-    error::Error, fs::OpenOptions, io::{BufRead, BufReader, BufWriter, Write}, vec
+    error::Error, fs::OpenOptions, io::{BufRead, BufReader, BufWriter, Write}, ops::RangeInclusive, vec
 };
 
+use proptest::prelude::*;
 use rand::{seq::SliceRandom, Rng};
 
 use crate::println_cod;
@@ -40,6 +41,53 @@ pub fn check_multicover(solution: Vec<Vec<usize>>, cod: bool) -> bool {
     true
 }
 
+// A r * c matrix of 0 and 1, about 30% of it are ones
+pub fn random_matrix(rng: &mut impl Rng, r: usize, c: usize) -> Vec<Vec<usize>> {
+    (0..r)
+        .map(|_| (0..c).map(|_| rng.gen_bool(0.3) as usize).collect())
+        .collect()
+}
+
+// The same matrices for proptest, drawn from its own rng so they shrink and replay
+pub fn matrix_strategy(
+    rows: RangeInclusive<usize>,
+    cols: RangeInclusive<usize>,
+) -> impl Strategy<Value = Vec<Vec<usize>>> {
+    (rows, cols).prop_flat_map(|(r, c)| {
+        let item = prop::bool::weighted(0.3).prop_map(usize::from);
+        prop::collection::vec(prop::collection::vec(item, c), r)
+    })
+}
+
+// Enumerate all exact covers by trying every subset of rows.
+// Row ids in the result start with 1 and each solution is sorted,
+// so it can be compared with the output of DL directly.
+// Empty rows are never part of a solution, as DL can not choose them.
+pub fn brute_force_exact_covers(matrix: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let rows = matrix.len();
+    let cols = matrix.first().map_or(0, |row| row.len());
+    let mut res = vec![];
+    for mask in 0u64..(1 << rows) {
+        let mut sum = vec![0usize; cols];
+        let mut sol = vec![];
+        for (r, row) in matrix.iter().enumerate() {
+            if mask & (1 << r) != 0 {
+                if row.iter().all(|&x| x == 0) {
+                    sol.clear();
+                    break;
+                }
+                row.iter().enumerate().for_each(|(idx, x)| sum[idx] += x);
+                sol.push(r + 1);
+            }
+        }
+        if !sol.is_empty() && sum.iter().all(|&x| x == 1) {
+            res.push(sol);
+        }
+    }
+    res.sort();
+    res
+}
+
 // Generate a sparse matrix and ensure there is a solution
 pub fn generate_sparse_matrix_with_solution(
     rows: usize,
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::error::DlxError;
+
 // Custom function to format a 2D Vec and return a string
 pub fn format_2d_string<T: std::fmt::Display>(vec_2d: &Vec<Vec<T>>) -> String {
     // Calculate the maximum width of each column
@@ -53,5 +55,78 @@ fn calculate_col_widths<T: ToString>(vec_2d: &Vec<Vec<T>>) -> Vec<usize> {
     col_widths
 }
 
+// A dense 0/1 matrix, row by row
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Matrix(pub Vec<Vec<usize>>);
+
+// The number of cols of a dense matrix and the cols (from 1) of the ones in each row.
+// All the rows must be as long as the first one and hold only 0 and 1.
+pub(crate) fn dense_rows<T: Copy + Into<usize>>(
+    matrix: &[Vec<T>],
+) -> Result<(usize, Vec<Vec<usize>>), DlxError> {
+    let col_size = matrix.first().map_or(0, Vec::len);
+    let mut rows = Vec::with_capacity(matrix.len());
+    for (r_in, c_vec) in matrix.iter().enumerate() {
+        if c_vec.len() != col_size {
+            return Err(DlxError::InvalidInput(format!(
+                "row {} has {} cols, but row 1 has {col_size}",
+                r_in + 1,
+                c_vec.len()
+            )));
+        }
+        let mut cols = vec![];
+        for (c_in, &item) in c_vec.iter().enumerate() {
+            match item.into() {
+                0 => {}
+                1 => cols.push(c_in + 1),
+                v => {
+                    return Err(DlxError::InvalidInput(format!(
+                        "({}, {}) is {v}, but only 0 and 1 are allowed",
+                        r_in + 1,
+                        c_in + 1
+                    )))
+                }
+            }
+        }
+        rows.push(cols);
+    }
+    Ok((col_size, rows))
+}
+
+// The cols (from 1) of each row of a CSR matrix: the ones of row i + 1 are at
+// indices[indptr[i]..indptr[i + 1]], and the indices are cols from 0 as in scipy.sparse.
+pub(crate) fn sparse_rows(
+    col_size: usize,
+    indptr: &[usize],
+    indices: &[usize],
+) -> Result<Vec<Vec<usize>>, DlxError> {
+    if indptr.first() != Some(&0) || indptr.last() != Some(&indices.len()) {
+        return Err(DlxError::InvalidInput(format!(
+            "indptr must go from 0 to the {} indices",
+            indices.len()
+        )));
+    }
+    let mut rows = Vec::with_capacity(indptr.len() - 1);
+    for (r_in, range) in indptr.windows(2).enumerate() {
+        if range[0] > range[1] {
+            return Err(DlxError::InvalidInput(format!(
+                "indptr of row {} goes back from {} to {}",
+                r_in + 1,
+                range[0],
+                range[1]
+            )));
+        }
+        let mut cols = Vec::with_capacity(range[1] - range[0]);
+        for &c_in in &indices[range[0]..range[1]] {
+            if c_in >= col_size {
+                return Err(DlxError::InvalidInput(format!(
+                    "row {} has col index {c_in}, but there are {col_size} cols",
+                    r_in + 1
+                )));
+            }
+            cols.push(c_in + 1);
+        }
+        rows.push(cols);
+    }
+    Ok(rows)
+}